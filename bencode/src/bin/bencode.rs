@@ -0,0 +1,554 @@
+//! Command-line tool for inspecting and producing bencode.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::process::ExitCode;
+
+use bencode::{Options, Token, Tokenizer, Value};
+use sha1::{Digest, Sha1};
+
+const USAGE: &str = "\
+usage: bencode <command> [args] [FILE]
+
+Reads FILE, or standard input if it is omitted or `-`.
+
+commands:
+  decode [--json] [FILE]   print the value as indented text, or as JSON
+  encode [FILE]            encode JSON, as printed by `decode --json`
+  get <PATH> [FILE]        print the value at PATH, such as info.files.0.length
+  check [FILE]             list every deviation from the canonical encoding
+  hash <PATH> [FILE]       print the SHA-1 of the encoded value at PATH, such as info
+";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["decode", "--json", file @ ..] if file.len() <= 1 => decode(file.first(), true),
+        ["decode", file @ ..] if file.len() <= 1 => decode(file.first(), false),
+        ["encode", file @ ..] if file.len() <= 1 => encode(file.first()),
+        ["get", path, file @ ..] if file.len() <= 1 => get(path, file.first()),
+        ["check", file @ ..] if file.len() <= 1 => check(file.first()),
+        ["hash", path, file @ ..] if file.len() <= 1 => hash(path, file.first()),
+        ["help" | "--help" | "-h"] => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        _ => {
+            eprint!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("bencode: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Each command returns whether it succeeded, or an error message.
+type Result<T> = std::result::Result<T, String>;
+
+fn read_input(file: Option<&&str>) -> Result<Vec<u8>> {
+    match file {
+        None | Some(&"-") => {
+            let mut input = Vec::new();
+            io::stdin()
+                .read_to_end(&mut input)
+                .map_err(|err| format!("cannot read standard input: {}", err))?;
+            Ok(input)
+        }
+        Some(path) => fs::read(path).map_err(|err| format!("cannot read {}: {}", path, err)),
+    }
+}
+
+fn write_output(output: &[u8]) -> Result<bool> {
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(output)
+        .and_then(|()| stdout.flush())
+        .map_err(|err| format!("cannot write output: {}", err))?;
+    Ok(true)
+}
+
+fn decode(file: Option<&&str>, json: bool) -> Result<bool> {
+    write_output(&decoded(&read_input(file)?, json)?)
+}
+
+fn decoded(input: &[u8], json: bool) -> Result<Vec<u8>> {
+    let node = parse(input)?;
+    let text = if json {
+        serde_json::to_string_pretty(&node.to_json()?).unwrap()
+    } else {
+        format!("{:#}", node)
+    };
+    Ok(format!("{}\n", text).into_bytes())
+}
+
+fn encode(file: Option<&&str>) -> Result<bool> {
+    write_output(&encoded(&read_input(file)?)?)
+}
+
+fn encoded(input: &[u8]) -> Result<Vec<u8>> {
+    let json: serde_json::Value = serde_json::from_slice(input).map_err(|err| err.to_string())?;
+    let value = Value::try_from(json).map_err(|err| err.to_string())?;
+    bencode::to_bytes(&value).map_err(|err| err.to_string())
+}
+
+fn get(path: &str, file: Option<&&str>) -> Result<bool> {
+    let input = read_input(file)?;
+    let span = find(&input, path)?;
+    write_output(format!("{:#}\n", parse(&input[span])?).as_bytes())
+}
+
+fn hash(path: &str, file: Option<&&str>) -> Result<bool> {
+    write_output(format!("{}\n", digest(&read_input(file)?, path)?).as_bytes())
+}
+
+// The SHA-1 of the encoded value at `path`, in hexadecimal.
+fn digest(input: &[u8], path: &str) -> Result<String> {
+    let span = find(input, path)?;
+    let mut hex = String::new();
+    for byte in Sha1::digest(&input[span]) {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    Ok(hex)
+}
+
+// Finds the span of the value at a dotted path of dictionary keys and list
+// indices, without decoding anything else.
+fn find(input: &[u8], path: &str) -> Result<Range<usize>> {
+    let mut tokens = Tokenizer::new(input);
+    let segments: Vec<&str> = path.split('.').filter(|s| !s.is_empty()).collect();
+    for (depth, segment) in segments.iter().enumerate() {
+        let missing = || format!("no value at `{}`", segments[..=depth].join("."));
+        match next(&mut tokens)? {
+            (_, Token::DictStart) => loop {
+                match next(&mut tokens)? {
+                    (_, Token::Key(key)) if key == segment.as_bytes() => break,
+                    (_, Token::Key(_)) => skip(&mut tokens)?,
+                    _ => return Err(missing()),
+                }
+            },
+            (_, Token::ListStart) => {
+                let index: usize = segment.parse().map_err(|_| missing())?;
+                for _ in 0..index {
+                    if input.get(tokens.offset()) == Some(&b'e') {
+                        return Err(missing());
+                    }
+                    skip(&mut tokens)?;
+                }
+                if input.get(tokens.offset()) == Some(&b'e') {
+                    return Err(missing());
+                }
+            }
+            _ => return Err(missing()),
+        }
+    }
+    skip_value(&mut tokens)
+}
+
+fn next<'a>(tokens: &mut Tokenizer<'a>) -> Result<(usize, Token<'a>)> {
+    match tokens.next_token() {
+        Ok(Some(token)) => Ok(token),
+        Ok(None) => Err("unexpected end of input".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn skip(tokens: &mut Tokenizer) -> Result<()> {
+    skip_value(tokens).map(|_| ())
+}
+
+fn skip_value(tokens: &mut Tokenizer) -> Result<Range<usize>> {
+    tokens.skip_value().map_err(|err| err.to_string())
+}
+
+// A decoded value. It is like `Value`, except that integers outside the
+// range of `i64` are kept as their digits, since BEP 3 puts no bound on
+// integers and such values turn up in real files.
+enum Node<'a> {
+    Int(i64),
+    BigInt(&'a [u8]),
+    Bytes(&'a [u8]),
+    List(Vec<Node<'a>>),
+    Dict(BTreeMap<&'a [u8], Node<'a>>),
+}
+
+// Decodes a single value like `bencode::from_bytes::<Value>` does.
+fn parse(input: &[u8]) -> Result<Node<'_>> {
+    let mut tokens = Tokenizer::new(input);
+    let (_, token) = next(&mut tokens)?;
+    let node = parse_node(&mut tokens, token)?;
+    match tokens.next_token() {
+        Err(err) => Err(err.to_string()),
+        Ok(_) => Ok(node),
+    }
+}
+
+fn parse_node<'a>(tokens: &mut Tokenizer<'a>, token: Token<'a>) -> Result<Node<'a>> {
+    Ok(match token {
+        Token::Int(int) => Node::Int(int),
+        Token::BigInt(digits) => Node::BigInt(digits),
+        Token::Bytes(bytes) => Node::Bytes(bytes),
+        Token::ListStart => {
+            let mut list = Vec::new();
+            loop {
+                match next(tokens)? {
+                    (_, Token::End) => break Node::List(list),
+                    (_, token) => list.push(parse_node(tokens, token)?),
+                }
+            }
+        }
+        Token::DictStart => {
+            let mut dict = BTreeMap::new();
+            loop {
+                match next(tokens)? {
+                    (_, Token::End) => break Node::Dict(dict),
+                    (_, Token::Key(key)) => {
+                        let (_, token) = next(tokens)?;
+                        dict.insert(key, parse_node(tokens, token)?);
+                    }
+                    (offset, _) => return Err(format!("expected key at byte offset {}", offset)),
+                }
+            }
+        }
+        // The tokenizer only yields these inside a dictionary or list.
+        Token::Key(_) | Token::End => unreachable!("unexpected token"),
+    })
+}
+
+impl Node<'_> {
+    // Converts to JSON like `serde_json::Value::from(Value)` does. Integers
+    // that fit in neither an `i64` nor a `u64` have no JSON number.
+    fn to_json(&self) -> Result<serde_json::Value> {
+        Ok(match self {
+            Node::Int(int) => serde_json::Value::from(*int),
+            Node::BigInt(digits) => {
+                let digits = String::from_utf8_lossy(digits);
+                match digits.parse::<u64>() {
+                    Ok(int) => serde_json::Value::from(int),
+                    Err(_) => return Err(format!("integer {} does not fit in JSON", digits)),
+                }
+            }
+            Node::Bytes(bytes) => serde_json::Value::from(Value::from(*bytes)),
+            Node::List(list) => list
+                .iter()
+                .map(Node::to_json)
+                .collect::<Result<_>>()
+                .map(serde_json::Value::Array)?,
+            Node::Dict(dict) => {
+                // Let `Value` decide how the keys are written, then fill in
+                // the values, which the placeholder dictionary leaves empty.
+                let keys: BTreeMap<Vec<u8>, Value> = dict
+                    .keys()
+                    .map(|key| (key.to_vec(), Value::List(Vec::new())))
+                    .collect();
+                let mut json = serde_json::Value::from(Value::Dict(keys));
+                let mut values = dict.values();
+                let slots: Vec<&mut serde_json::Value> = match &mut json {
+                    // Escaped as `{"$dict": [[key, value], ...]}`. A plain
+                    // object with `$dict` as its only key would be escaped.
+                    serde_json::Value::Object(object)
+                        if object.len() == 1 && object.contains_key("$dict") =>
+                    {
+                        object
+                            .values_mut()
+                            .flat_map(|entries| entries.as_array_mut().unwrap())
+                            .map(|entry| &mut entry.as_array_mut().unwrap()[1])
+                            .collect()
+                    }
+                    serde_json::Value::Object(object) => object.values_mut().collect(),
+                    _ => unreachable!("a dictionary converts to an object"),
+                };
+                for slot in slots {
+                    *slot = values.next().unwrap().to_json()?;
+                }
+                json
+            }
+        })
+    }
+}
+
+// Renders the value like the `Display` implementation of `Value` does.
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let indent = if formatter.alternate() { Some(0) } else { None };
+        self.write(formatter, None, indent)
+    }
+}
+
+impl Node<'_> {
+    fn write(
+        &self,
+        out: &mut fmt::Formatter,
+        key: Option<&[u8]>,
+        indent: Option<usize>,
+    ) -> fmt::Result {
+        let inner = indent.map(|indent| indent + 1);
+        match self {
+            Node::Int(int) => write!(out, "{}", int),
+            Node::BigInt(digits) => out.write_str(&String::from_utf8_lossy(digits)),
+            Node::Bytes(bytes)
+                if key == Some(b"pieces") && !bytes.is_empty() && bytes.len() % 20 == 0 =>
+            {
+                write!(out, "<{} piece hashes>", bytes.len() / 20)
+            }
+            Node::Bytes(bytes) => write!(out, "{}", Value::from(*bytes)),
+            Node::List(list) if list.is_empty() => out.write_str("[]"),
+            Node::List(list) => {
+                out.write_char('[')?;
+                for (i, element) in list.iter().enumerate() {
+                    write_separator(out, i, inner)?;
+                    element.write(out, None, inner)?;
+                }
+                write_close(out, ']', indent)
+            }
+            Node::Dict(dict) if dict.is_empty() => out.write_str("{}"),
+            Node::Dict(dict) => {
+                out.write_char('{')?;
+                for (i, (key, value)) in dict.iter().enumerate() {
+                    write_separator(out, i, inner)?;
+                    write!(out, "{}: ", Value::from(*key))?;
+                    value.write(out, Some(key), inner)?;
+                }
+                write_close(out, '}', indent)
+            }
+        }
+    }
+}
+
+fn write_separator(out: &mut fmt::Formatter, i: usize, indent: Option<usize>) -> fmt::Result {
+    if i > 0 {
+        out.write_char(',')?;
+    }
+    match indent {
+        Some(indent) => write!(out, "\n{:1$}", "", indent * 2),
+        None if i > 0 => out.write_char(' '),
+        None => Ok(()),
+    }
+}
+
+fn write_close(out: &mut fmt::Formatter, close: char, indent: Option<usize>) -> fmt::Result {
+    if let Some(indent) = indent {
+        write!(out, "\n{:1$}", "", indent * 2)?;
+    }
+    out.write_char(close)
+}
+
+// An open container while checking the input.
+enum Frame<'a> {
+    List,
+    Dict { last_key: Option<&'a [u8]> },
+}
+
+// Reports every place where the input deviates from the canonical encoding,
+// rather than stopping at the first one like `Mode::Strict` does.
+fn check(file: Option<&&str>) -> Result<bool> {
+    let report = check_input(&read_input(file)?);
+    for note in &report.notes {
+        println!("note: {}", note);
+    }
+    if report.violations.is_empty() {
+        println!("ok");
+        return Ok(true);
+    }
+    for violation in &report.violations {
+        println!("{}", violation);
+    }
+    Ok(false)
+}
+
+// The findings of `check`, each in input order.
+#[derive(Debug, Default)]
+struct Report {
+    // Deviations from the canonical encoding.
+    violations: Vec<String>,
+    // Canonical values that other tools may still reject.
+    notes: Vec<String>,
+}
+
+fn check_input(input: &[u8]) -> Report {
+    let mut tokens = Tokenizer::with_options(input, Options::lenient().max_depth(usize::MAX));
+    let mut report = Report::default();
+    let violations = &mut report.violations;
+    let mut stack = Vec::new();
+    let mut complete = true;
+    loop {
+        let (start, token) = match tokens.next_token() {
+            Ok(Some(token)) => token,
+            Ok(None) => break,
+            Err(err) => {
+                violations.push(err.to_string());
+                complete = false;
+                break;
+            }
+        };
+        let raw = &input[start..tokens.offset()];
+        match token {
+            Token::Int(int) if raw != format!("i{}e", int).as_bytes() => {
+                violations.push(format!("non-canonical integer (byte offset {})", start));
+            }
+            Token::BigInt(digits) => {
+                if digits
+                    .strip_prefix(b"-")
+                    .unwrap_or(digits)
+                    .starts_with(b"0")
+                {
+                    violations.push(format!("non-canonical integer (byte offset {})", start));
+                }
+                report.notes.push(format!(
+                    "integer does not fit in 64 bits, which this tool and many others do \
+                     not support (byte offset {})",
+                    start
+                ));
+            }
+            Token::Bytes(bytes) | Token::Key(bytes)
+                if raw[..raw.len() - bytes.len()] != *format!("{}:", bytes.len()).as_bytes() =>
+            {
+                violations.push(format!(
+                    "string length has a leading zero (byte offset {})",
+                    start
+                ));
+            }
+            _ => {}
+        }
+        match (token, stack.last_mut()) {
+            (Token::Key(key), Some(Frame::Dict { last_key })) => {
+                match last_key {
+                    Some(last) if *last == key => {
+                        violations.push(format!("duplicate dictionary key (byte offset {})", start))
+                    }
+                    Some(last) if *last > key => violations.push(format!(
+                        "dictionary keys are not sorted (byte offset {})",
+                        start
+                    )),
+                    _ => {}
+                }
+                *last_key = Some(key);
+            }
+            (Token::DictStart, _) => stack.push(Frame::Dict { last_key: None }),
+            (Token::ListStart, _) => stack.push(Frame::List),
+            (Token::End, _) => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    if complete && tokens.offset() < input.len() {
+        violations.push(format!(
+            "trailing data after value (byte offset {})",
+            tokens.offset()
+        ));
+    }
+    report
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_find() {
+    let input = include_bytes!("../../fixtures/multi-file.torrent");
+    let span = find(input, "info.files.2.length").unwrap();
+    assert_eq!(&input[span], b"i70001e");
+    let span = find(input, "").unwrap();
+    assert_eq!(span, 0..input.len());
+    assert!(find(input, "info.files.3").is_err());
+    assert!(find(input, "info.files.x").is_err());
+    assert!(find(input, "announce.0").is_err());
+}
+
+#[test]
+fn test_decode() {
+    let input = b"d4:listli1ei-2ee4:name5:hello3:raw2:\xff\xfee";
+    let text = decoded(input, false).unwrap();
+    assert_eq!(
+        text,
+        format!("{:#}\n", bencode::from_bytes::<Value>(input).unwrap()).as_bytes()
+    );
+    let json: serde_json::Value = serde_json::from_slice(&decoded(input, true).unwrap()).unwrap();
+    assert_eq!(json["name"], "hello");
+    assert_eq!(json["list"], serde_json::json!([1, -2]));
+    assert!(decoded(b"i1", false).is_err());
+
+    // The JSON matches the conversion of `Value`, escapes included.
+    for input in [
+        &include_bytes!("../../fixtures/multi-file.torrent")[..],
+        b"d2:\xff\xfeli1ee1:ai2ee",
+        b"d4:$hex1:ae",
+        b"d5:$dicti1ee",
+        b"d5:$dicti1e1:ali2eee",
+    ] {
+        let json: serde_json::Value =
+            serde_json::from_slice(&decoded(input, true).unwrap()).unwrap();
+        let value: Value = bencode::from_bytes(input).unwrap();
+        assert_eq!(json, serde_json::Value::from(value));
+    }
+
+    // Integers beyond `i64` are printed from their digits.
+    let input = b"d3:bigi18446744073709551615e4:hugei-99999999999999999999999ee";
+    let text = String::from_utf8(decoded(input, false).unwrap()).unwrap();
+    assert!(text.contains(r#""big": 18446744073709551615,"#));
+    assert!(text.contains(r#""huge": -99999999999999999999999"#));
+    let json: serde_json::Value =
+        serde_json::from_slice(&decoded(b"li18446744073709551615ee", true).unwrap()).unwrap();
+    assert_eq!(json, serde_json::json!([u64::MAX]));
+    assert!(decoded(input, true).is_err());
+}
+
+#[test]
+fn test_encode() {
+    let input = include_bytes!("../../fixtures/multi-file.torrent");
+    assert_eq!(encoded(&decoded(input, true).unwrap()).unwrap(), input);
+    assert_eq!(
+        encoded(br#"{"b": 1, "a": ["x"]}"#).unwrap(),
+        b"d1:al1:xe1:bi1ee"
+    );
+    assert!(encoded(b"1.5").is_err());
+}
+
+#[test]
+fn test_hash() {
+    let input = include_bytes!("../../fixtures/multi-file.torrent");
+    let span = find(input, "info").unwrap();
+    let expected: String = Sha1::digest(&input[span])
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    assert_eq!(digest(input, "info").unwrap(), expected);
+    assert!(digest(input, "nope").is_err());
+}
+
+#[test]
+fn test_check() {
+    let input = include_bytes!("../../fixtures/multi-file.torrent");
+    let report = check_input(input);
+    assert!(report.violations.is_empty());
+    assert!(report.notes.is_empty());
+
+    let input = b"d1:bi01e1:ai18446744073709551615e1:a1:x1:cli-099999999999999999999eee";
+    let report = check_input(input);
+    assert_eq!(
+        report.violations,
+        [
+            "non-canonical integer (byte offset 4)",
+            "dictionary keys are not sorted (byte offset 8)",
+            "duplicate dictionary key (byte offset 33)",
+            "non-canonical integer (byte offset 43)",
+        ]
+    );
+    assert_eq!(report.notes.len(), 2);
+    assert!(report.notes[0].ends_with("(byte offset 11)"));
+    assert!(report.notes[1].ends_with("(byte offset 43)"));
+
+    // BEP 3 puts no bound on integers, so a large one is canonical.
+    let report = check_input(b"i18446744073709551615e");
+    assert!(report.violations.is_empty());
+    assert_eq!(report.notes.len(), 1);
+}
@@ -1,14 +1,200 @@
-use std::ops::{AddAssign, MulAssign, Neg};
+use alloc::borrow::Cow;
+use core::str;
+#[cfg(feature = "std")]
+use std::io;
 
-use serde::de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
-use serde::Deserialize;
+use serde::de::value::SeqDeserializer;
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, Unexpected,
+    VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, ErrorKind, Result};
+#[cfg(feature = "std")]
+use crate::incremental::{Progress, Scanner};
+use crate::raw;
+use crate::token::{self, Tokenizer};
+
+/// How closely the input has to follow the canonical bencode encoding.
+///
+/// | Input                                    | `Strict` | `Standard` | `Lenient` |
+/// |------------------------------------------|----------|------------|-----------|
+/// | unsorted or duplicate dictionary keys    | error    | accepted   | accepted  |
+/// | zero-padded string lengths, as `04:spam` | error    | accepted   | accepted  |
+/// | leading zeros and `-0` in integers       | error    | error      | accepted  |
+/// | trailing data after the value            | error    | error      | accepted  |
+///
+/// Integer dictionary keys are held to the same rules as integers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Requires the canonical encoding, so that re-encoding a decoded value
+    /// gives back the same bytes, as when checking an info hash.
+    Strict,
+    /// Rejects what BEP 3 spells out as invalid, integers such as `i03e` or
+    /// `i-0e` and data after the value, which usually means the input is
+    /// corrupt or is not bencode at all. Unsorted keys and padded lengths
+    /// are accepted: they come from careless encoders, are common in the
+    /// wild and still decode to a single value. This is the default, since
+    /// `Strict` would refuse real torrents and `Lenient` would hide
+    /// truncated or concatenated input.
+    #[default]
+    Standard,
+    /// Accepts anything that can be unambiguously decoded, including
+    /// non-canonical integers and trailing data after the value.
+    Lenient,
+}
+
+/// Options controlling how a [`Deserializer`] validates its input.
+///
+/// ```
+/// # use bencode::{ErrorKind, Mode, Options};
+/// let options = Options::new().mode(Mode::Strict);
+/// let result = options.from_bytes::<bencode::Value>(b"d1:bi1e1:ai2ee");
+/// assert_eq!(result.unwrap_err().kind(), &ErrorKind::UnsortedKey);
+/// ```
+///
+/// Limits guard against hostile input, such as a peer or tracker sending
+/// thousands of nested lists or a huge string length prefix. They are
+/// checked before the corresponding data is read or allocated. Only the
+/// nesting depth is limited by default.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub(crate) mode: Mode,
+    pub(crate) max_depth: usize,
+    pub(crate) max_string_length: usize,
+    pub(crate) max_items: usize,
+    pub(crate) max_input_size: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mode: Mode::default(),
+            max_depth: 256,
+            max_string_length: usize::MAX,
+            max_items: usize::MAX,
+            max_input_size: usize::MAX,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Options::default()
+    }
+
+    /// Shorthand for `Options::new().mode(Mode::Strict)`.
+    pub fn strict() -> Self {
+        Options::new().mode(Mode::Strict)
+    }
+
+    /// Shorthand for `Options::new().mode(Mode::Lenient)`.
+    pub fn lenient() -> Self {
+        Options::new().mode(Mode::Lenient)
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Limits how deeply lists and dictionaries may be nested. Defaults to
+    /// 256.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limits the length of any single byte string.
+    pub fn max_string_length(mut self, max_string_length: usize) -> Self {
+        self.max_string_length = max_string_length;
+        self
+    }
+
+    /// Limits the total number of list elements and dictionary entries in
+    /// the whole input.
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    /// Limits the size of the input accepted by [`Options::from_bytes`] and
+    /// [`Options::from_reader`], which stops reading once the limit is
+    /// exceeded, and the input an
+    /// [`IncrementalDecoder`](crate::IncrementalDecoder) buffers while
+    /// waiting for the end of a value.
+    pub fn max_input_size(mut self, max_input_size: usize) -> Self {
+        self.max_input_size = max_input_size;
+        self
+    }
+
+    /// Deserializes an instance of `T` from `input` using these options.
+    ///
+    /// Trailing data after the value is an error unless the mode is
+    /// [`Mode::Lenient`].
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_bytes<'a, T>(&self, input: &'a [u8]) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        if input.len() > self.max_input_size {
+            return Err(Error::new(
+                ErrorKind::InputTooLarge,
+                Some(self.max_input_size),
+            ));
+        }
+        let mut deserializer = Deserializer::with_options(input, self.clone());
+        let t = T::deserialize(&mut deserializer).map_err(|err| err.at(0))?;
+        if self.mode != Mode::Lenient {
+            deserializer.end()?;
+        }
+        Ok(t)
+    }
+
+    /// Reads one value from `reader` and deserializes an instance of `T`
+    /// from it using these options.
+    ///
+    /// Nothing past the end of the value is read, so the rest of a stream,
+    /// such as the next message from a peer, is left for the caller. Reads
+    /// are never larger than the value still needs, so an unbuffered reader
+    /// is best wrapped in a [`BufReader`](std::io::BufReader).
+    #[cfg(feature = "std")]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_reader<R, T>(&self, mut reader: R) -> Result<T>
+    where
+        R: io::Read,
+        T: de::DeserializeOwned,
+    {
+        // Bounds each read, in case a string announces a huge length.
+        const MAX_READ: usize = 8 << 10;
+
+        let mut input = Vec::new();
+        let mut scanner = Scanner::default();
+        while let Progress::NeedMoreData = scanner.scan(&input, self)? {
+            let start = input.len();
+            input.resize(start + scanner.missing().min(MAX_READ), 0);
+            let read = loop {
+                match reader.read(&mut input[start..]) {
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    result => break result?,
+                }
+            };
+            input.truncate(start + read);
+            if read == 0 {
+                return Err(Error::new(ErrorKind::Eof, Some(start)));
+            }
+        }
+        self.from_bytes(&input)
+    }
+}
 
 pub struct Deserializer<'de> {
-    // This string starts with the input data and characters are truncated off
-    // the beginning as data is parsed.
-    input: &'de str,
+    // Reads the input and enforces the options. The deserializer drives it
+    // through its lower-level parsing methods rather than token by token, so
+    // that integers and strings can be parsed straight into the type that
+    // the visitor asks for.
+    tokens: Tokenizer<'de>,
 }
 
 impl<'de> Deserializer<'de> {
@@ -16,112 +202,80 @@ impl<'de> Deserializer<'de> {
     // That way basic use cases are satisfied by something like
     // `serde_json::from_str(...)` while advanced use cases that require a
     // deserializer can make one with `serde_json::Deserializer::from_str(...)`.
+    pub fn from_bytes(input: &'de [u8]) -> Self {
+        Deserializer::with_options(input, Options::default())
+    }
+
+    pub fn with_options(input: &'de [u8], options: Options) -> Self {
+        Deserializer {
+            tokens: Tokenizer::with_options(input, options),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(input: &'de str) -> Self {
-        Deserializer { input }
+        Deserializer::from_bytes(input.as_bytes())
+    }
+
+    /// Checks that the whole input has been consumed.
+    pub fn end(&self) -> Result<()> {
+        self.tokens.end()
     }
 }
 
 // By convention, the public API of a Serde deserializer is one or more
 // `from_xyz` methods such as `from_str`, `from_bytes`, or `from_reader`
 // depending on what Rust types the deserializer is able to consume as input.
-//
-// This basic deserializer supports only `from_str`.
-pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+pub fn from_bytes<'a, T>(b: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_str(s);
-    let t = T::deserialize(&mut deserializer)?;
-    if deserializer.input.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingCharacters)
-    }
+    Options::default().from_bytes(b)
 }
 
-impl<'de> Deserializer<'de> {
-    fn peek_char(&mut self) -> Result<char> {
-        self.input.chars().next().ok_or(Error::Eof)
-    }
+pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_bytes(s.as_bytes())
+}
 
-    fn next_char(&mut self) -> Result<char> {
-        let ch = self.peek_char()?;
-        self.input = &self.input[ch.len_utf8()..];
-        Ok(ch)
-    }
+/// Deserializes an instance of `T` from the next value `reader` yields,
+/// leaving anything after it unread.
+///
+/// The result cannot borrow from the input, so `T` must own its data.
+#[cfg(feature = "std")]
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    Options::default().from_reader(reader)
+}
 
+impl<'de> Deserializer<'de> {
+    // Booleans have no encoding of their own and are written as the integers
+    // `i0e` and `i1e`.
     fn parse_bool(&mut self) -> Result<bool> {
-        unimplemented!()
-    }
-
-    fn parse_unsigned<T>(&mut self) -> Result<T>
-    where
-        T: AddAssign<T> + MulAssign<T> + From<u8>,
-    {
-        if self.next_char()? != 'i' {
-            return Err(Error::ExpectedInteger);
-        }
-        let mut int = match self.next_char()? {
-            ch @ '0'..='9' => T::from(ch as u8 - b'0'),
-            _ => {
-                return Err(Error::ExpectedInteger);
-            }
-        };
-        loop {
-            match self.input.chars().next() {
-                Some(ch @ '0'..='9') => {
-                    self.input = &self.input[1..];
-                    int *= T::from(10);
-                    int += T::from(ch as u8 - b'0');
-                }
-                Some('e') => {
-                    return Ok(int);
-                }
-                _ => return Err(Error::ExpectedInteger),
-            }
+        let start = self.tokens.index;
+        match self.tokens.parse_integer::<i64>() {
+            Ok(0) => Ok(false),
+            Ok(1) => Ok(true),
+            Ok(_) | Err(_) => Err(Error::new(ErrorKind::ExpectedBoolean, Some(start))),
         }
     }
 
-    fn parse_signed<T>(&mut self) -> Result<T>
-    where
-        T: Neg<Output = T> + AddAssign<T> + MulAssign<T> + From<i8>,
-    {
-        unimplemented!()
-    }
-
-    fn parse_bytes(&mut self) -> Result<&'de [u8]> {
-        let len = match self.next_char()? {
-            ch @ '0'..='9' => ch.to_digit(10).unwrap() as usize,
-            _ => {
-                return Err(Error::ExpectedString);
-            }
-        };
-        if self.next_char()? != ':' {
-            return Err(Error::ExpectedString);
-        };
-        let s = self.input[..len].as_bytes();
-        self.input = &self.input[len..];
-        println!("{:?}", s);
-        Ok(s)
-    }
-
-    fn parse_string(&mut self) -> Result<&'de str> {
-        let len = match self.next_char()? {
-            ch @ '0'..='9' => ch.to_digit(10).unwrap() as usize,
-            _ => {
-                return Err(Error::ExpectedString);
-            }
-        };
-        if self.next_char()? != ':' {
-            return Err(Error::ExpectedString);
-        };
-        let s = &self.input[..len];
-        self.input = &self.input[len..];
-        Ok(s)
+    // Returns the next dictionary key without consuming it. The entry it
+    // starts is counted, since the caller reads it next.
+    fn peek_key(&mut self) -> Result<&'de [u8]> {
+        let start = self.tokens.index;
+        let key = self.tokens.parse_key(None)?;
+        self.tokens.index = start;
+        Ok(key)
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     // Look at the input data to decide what Serde data model type to
@@ -131,29 +285,29 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match self.peek_char()? {
-            '0'..='9' => self.deserialize_str(visitor),
-            'i' => self.deserialize_u64(visitor),
-            'l' => self.deserialize_seq(visitor),
-            'd' => self.deserialize_map(visitor),
-            _ => Err(Error::Syntax),
+        match self.tokens.peek_byte()? {
+            b'0'..=b'9' => self.deserialize_bytes(visitor),
+            // Bencode integers are unbounded, so pick the narrowest visitor
+            // method that can hold the value.
+            b'i' => {
+                let start = self.tokens.index;
+                let int: i128 = self.tokens.parse_integer()?;
+                if let Ok(int) = i64::try_from(int) {
+                    visitor.visit_i64(int)
+                } else if let Ok(int) = u64::try_from(int) {
+                    visitor.visit_u64(int)
+                } else {
+                    Err(Error::new(ErrorKind::IntegerOverflow, Some(start)))
+                }
+            }
+            b'l' => self.deserialize_seq(visitor),
+            b'd' => self.deserialize_map(visitor),
+            _ => Err(Error::new(ErrorKind::Syntax, Some(self.tokens.index))),
         }
     }
 
-    // Uses the `parse_bool` parsing function defined above to read the JSON
-    // identifier `true` or `false` from the input.
-    //
-    // Parsing refers to looking at the input and deciding that it contains the
-    // JSON value `true` or `false`.
-    //
-    // Deserialization refers to mapping that JSON value into Serde's data
-    // model by invoking one of the `Visitor` methods. In the case of JSON and
-    // bool that mapping is straightforward so the distinction may seem silly,
-    // but in other cases Deserializers sometimes perform non-obvious mappings.
-    // For example the TOML format has a Datetime type and Serde's data model
-    // does not. In the `toml` crate, a Datetime in the input is deserialized by
-    // mapping it to a Serde data model "struct" type with a special name and a
-    // single field containing the Datetime represented as a string.
+    // Uses the `parse_bool` parsing function defined above to read `i0e` or
+    // `i1e` from the input.
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -161,88 +315,92 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_bool(self.parse_bool()?)
     }
 
-    // The `parse_signed` function is generic over the integer type `T` so here
-    // it is invoked with `T=i8`. The next 8 methods are similar.
+    // The `parse_integer` function is generic over the integer type `T` so
+    // here it is invoked with `T=i8`. The next 8 methods are similar.
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i8(self.parse_signed()?)
+        visitor.visit_i8(self.tokens.parse_integer()?)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i16(self.parse_signed()?)
+        visitor.visit_i16(self.tokens.parse_integer()?)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i32(self.parse_signed()?)
+        visitor.visit_i32(self.tokens.parse_integer()?)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i64(self.parse_signed()?)
+        visitor.visit_i64(self.tokens.parse_integer()?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u8(self.parse_unsigned()?)
+        visitor.visit_u8(self.tokens.parse_integer()?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u16(self.parse_unsigned()?)
+        visitor.visit_u16(self.tokens.parse_integer()?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u32(self.parse_unsigned()?)
+        visitor.visit_u32(self.tokens.parse_integer()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u64(self.parse_unsigned()?)
+        visitor.visit_u64(self.tokens.parse_integer()?)
     }
 
-    // Float parsing is stupidly hard.
+    // Bencode has no floating point numbers.
     fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::new(
+            ErrorKind::UnsupportedType,
+            Some(self.tokens.index),
+        ))
     }
 
-    // Float parsing is stupidly hard.
     fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::new(
+            ErrorKind::UnsupportedType,
+            Some(self.tokens.index),
+        ))
     }
 
-    // The `Serializer` implementation on the previous page serialized chars as
-    // single-character strings so handle that representation here.
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
+    // The `Serializer` writes chars as single-character strings. The visitor
+    // for `char` accepts a string and checks that it holds one character.
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // Parse a string, check that it is one character, call `visit_char`.
-        unimplemented!()
+        self.deserialize_str(visitor)
     }
 
     // Refer to the "Understanding deserializer lifetimes" page for information
@@ -251,7 +409,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_str(self.parse_string()?)
+        visitor.visit_borrowed_str(self.tokens.parse_string()?)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -261,60 +419,70 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_str(visitor)
     }
 
-    // The `Serializer` implementation on the previous page serialized byte
-    // arrays as JSON arrays of bytes. Handle that representation here.
+    // Bencode strings are byte strings, so bytes are borrowed straight from
+    // the input without any validation.
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_bytes(self.parse_bytes()?)
+        visitor.visit_borrowed_bytes(self.tokens.parse_bytes()?)
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_bytes(visitor)
     }
 
-    // An absent optional is represented as the JSON `null` and a present
-    // optional is represented as just the contained value.
-    //
-    // As commented in `Serializer` implementation, this is a lossy
-    // representation. For example the values `Some(())` and `None` both
-    // serialize as just `null`. Unfortunately this is typically what people
-    // expect when working with JSON. Other formats are encouraged to behave
-    // more intelligently if possible.
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value>
+    // Bencode has no null. `None` is written by leaving the dictionary entry
+    // out, and a missing `Option` field is filled in as `None` by the derived
+    // `Deserialize` implementation, so a value that is present is always
+    // `Some`.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_some(self)
     }
 
-    // In Serde, unit means an anonymous value containing no data.
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value>
+    // In Serde, unit means an anonymous value containing no data. It is
+    // written as the empty string `0:`.
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        let start = self.tokens.index;
+        match self.tokens.parse_bytes()? {
+            [] => visitor.visit_unit(),
+            bytes => Err(de::Error::invalid_value(Unexpected::Bytes(bytes), &visitor))
+                .map_err(|err: Error| err.at(start)),
+        }
     }
 
     // Unit struct means a named value containing no data.
-    fn deserialize_unit_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value>
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_unit(visitor)
     }
 
     // As is done here, serializers are encouraged to treat newtype structs as
     // insignificant wrappers around the data they contain. That means not
     // parsing anything other than the contained value.
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    //
+    // The one exception is `RawValue`, which receives the exact span of input
+    // bytes holding the next value.
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == raw::TOKEN {
+            let start = self.tokens.index;
+            de::IgnoredAny::deserialize(&mut *self)?;
+            return visitor.visit_borrowed_bytes(&self.tokens.input[start..self.tokens.index]);
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -326,17 +494,25 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         // Parse the opening bracket of the sequence.
-        if self.next_char()? == 'l' {
+        if self.tokens.next_byte()? == b'l' {
+            self.tokens.enter(self.tokens.index - 1)?;
             // Give the visitor access to each element of the sequence.
             let value = visitor.visit_seq(CommaSeparated::new(self))?;
+            self.tokens.leave();
             // Parse the closing bracket of the sequence.
-            if self.next_char()? == 'e' {
+            if self.tokens.next_byte()? == b'e' {
                 Ok(value)
             } else {
-                Err(Error::ExpectedArrayEnd)
+                Err(Error::new(
+                    ErrorKind::ExpectedArrayEnd,
+                    Some(self.tokens.index - 1),
+                ))
             }
         } else {
-            Err(Error::ExpectedArray)
+            Err(Error::new(
+                ErrorKind::ExpectedArray,
+                Some(self.tokens.index - 1),
+            ))
         }
     }
 
@@ -374,17 +550,25 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         // Parse the opening brace of the map.
-        if self.next_char()? == 'd' {
+        if self.tokens.next_byte()? == b'd' {
+            self.tokens.enter(self.tokens.index - 1)?;
             // Give the visitor access to each entry of the map.
             let value = visitor.visit_map(CommaSeparated::new(self))?;
+            self.tokens.leave();
             // Parse the closing brace of the map.
-            if self.next_char()? == 'e' {
+            if self.tokens.next_byte()? == b'e' {
                 Ok(value)
             } else {
-                Err(Error::ExpectedMapEnd)
+                Err(Error::new(
+                    ErrorKind::ExpectedMapEnd,
+                    Some(self.tokens.index - 1),
+                ))
             }
         } else {
-            Err(Error::ExpectedMap)
+            Err(Error::new(
+                ErrorKind::ExpectedMap,
+                Some(self.tokens.index - 1),
+            ))
         }
     }
 
@@ -406,16 +590,40 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_map(visitor)
     }
 
+    // Enums are externally tagged: a unit variant is the string holding its
+    // name, any other variant is a dictionary with a single entry mapping the
+    // name to the contents.
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
         _variants: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        match self.tokens.peek_byte()? {
+            b'0'..=b'9' => visitor.visit_enum(self.tokens.parse_string()?.into_deserializer()),
+            b'd' => {
+                self.tokens.enter(self.tokens.index)?;
+                self.tokens.index += 1;
+                let start = self.tokens.index;
+                let key = self.peek_key()?;
+                let value = visitor
+                    .visit_enum(Enum::new(self))
+                    .map_err(|err| err.at(start).in_key(key))?;
+                self.tokens.leave();
+                if self.tokens.next_byte()? == b'e' {
+                    Ok(value)
+                } else {
+                    Err(Error::new(
+                        ErrorKind::ExpectedMapEnd,
+                        Some(self.tokens.index - 1),
+                    ))
+                }
+            }
+            _ => Err(Error::new(ErrorKind::ExpectedEnum, Some(self.tokens.index))),
+        }
     }
 
     // An identifier in Serde is the type that identifies a field of a struct or
@@ -453,11 +661,20 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 // element.
 struct CommaSeparated<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
+    // The raw bytes of the current dictionary key, used to check the key
+    // order in strict mode and to report the path of errors.
+    last_key: Option<&'de [u8]>,
+    // The number of list elements read so far.
+    count: usize,
 }
 
 impl<'a, 'de> CommaSeparated<'a, 'de> {
     fn new(de: &'a mut Deserializer<'de>) -> Self {
-        CommaSeparated { de }
+        CommaSeparated {
+            de,
+            last_key: None,
+            count: 0,
+        }
     }
 }
 
@@ -471,10 +688,16 @@ impl<'de, 'a> SeqAccess<'de> for CommaSeparated<'a, 'de> {
         T: DeserializeSeed<'de>,
     {
         // Check if there are no more elements.
-        if self.de.peek_char()? == 'e' {
+        if self.de.tokens.peek_byte()? == b'e' {
             return Ok(None);
         }
-        seed.deserialize(&mut *self.de).map(Some)
+        let start = self.de.tokens.index;
+        self.de.tokens.count_item(start)?;
+        let index = self.count;
+        self.count += 1;
+        seed.deserialize(&mut *self.de)
+            .map(Some)
+            .map_err(|err| err.at(start).in_index(index))
     }
 }
 
@@ -488,10 +711,18 @@ impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
         K: DeserializeSeed<'de>,
     {
         // Check if there are no more entries.
-        if self.de.peek_char()? == 'e' {
+        if self.de.tokens.peek_byte()? == b'e' {
             return Ok(None);
         }
-        seed.deserialize(&mut *self.de).map(Some)
+        let start = self.de.tokens.index;
+        let key = self.de.tokens.parse_key(self.last_key)?;
+        self.last_key = Some(key);
+        seed.deserialize(KeyDeserializer::new(
+            Cow::Borrowed(key),
+            self.de.tokens.options.mode,
+        ))
+        .map(Some)
+        .map_err(|err| err.at(start))
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
@@ -499,7 +730,177 @@ impl<'de, 'a> MapAccess<'de> for CommaSeparated<'a, 'de> {
         V: DeserializeSeed<'de>,
     {
         // Deserialize a map value.
+        let start = self.de.tokens.index;
+        let key = self.last_key.unwrap_or_default();
         seed.deserialize(&mut *self.de)
+            .map_err(|err| err.at(start).in_key(key))
+    }
+}
+
+// Deserializes a dictionary key, which is always a byte string, as any type
+// that a serializer can turn into one: strings, bytes, sequences of bytes such
+// as `Vec<u8>` or `[u8; 20]`, integers written in decimal, and unit variants.
+pub(crate) struct KeyDeserializer<'de> {
+    key: Cow<'de, [u8]>,
+    mode: Mode,
+}
+
+impl<'de> KeyDeserializer<'de> {
+    pub(crate) fn new(key: Cow<'de, [u8]>, mode: Mode) -> Self {
+        KeyDeserializer { key, mode }
+    }
+
+    fn as_str(&self) -> Result<&str> {
+        str::from_utf8(&self.key).map_err(|_| ErrorKind::InvalidUtf8.into())
+    }
+
+    // Integer keys follow the same rules as integer values in the same mode:
+    // an optional minus sign and decimal digits, without leading zeros or
+    // `-0` unless the mode is lenient.
+    fn parse_integer<T: TryFrom<i128>>(&self) -> Result<T> {
+        token::check_integer_digits(&self.key, self.mode)?;
+        token::integer_from_digits(&self.key).ok_or_else(|| ErrorKind::IntegerOverflow.into())
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for KeyDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_integer_key {
+    ($($deserialize:ident => $visit:ident,)*) => {
+        $(
+            fn $deserialize<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.parse_integer()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            Cow::Borrowed(key) => visitor.visit_borrowed_bytes(key),
+            Cow::Owned(key) => visitor.visit_byte_buf(key),
+        }
+    }
+
+    deserialize_integer_key! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            Cow::Borrowed(key) => match str::from_utf8(key) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => Err(ErrorKind::InvalidUtf8.into()),
+            },
+            Cow::Owned(_) => visitor.visit_str(self.as_str()?),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    // The key is a sequence of bytes, as in `Vec<u8>` or a hash `[u8; 20]`.
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut seq = SeqDeserializer::<_, Error>::new(self.key.iter().copied());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Only unit variants can be keys, written as the variant name.
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self.as_str()?.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 bytes byte_buf unit unit_struct map struct ignored_any
     }
 }
 
@@ -507,6 +908,12 @@ struct Enum<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
 }
 
+impl<'a, 'de> Enum<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>) -> Self {
+        Enum { de }
+    }
+}
+
 // `EnumAccess` is provided to the `Visitor` to give it the ability to determine
 // which variant of the enum is supposed to be deserialized.
 //
@@ -520,9 +927,9 @@ impl<'de, 'a> EnumAccess<'de> for Enum<'a, 'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        // The `deserialize_enum` method parsed a `{` character so we are
-        // currently inside of a map. The seed will be deserializing itself from
-        // the key of the map.
+        // The `deserialize_enum` method parsed a `d` character so we are
+        // currently inside of a dictionary. The seed will be deserializing
+        // itself from the key of the dictionary.
         let val = seed.deserialize(&mut *self.de)?;
         Ok((val, self))
     }
@@ -536,10 +943,13 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
     // If the `Visitor` expected this variant to be a unit variant, the input
     // should have been the plain string case handled in `deserialize_enum`.
     fn unit_variant(self) -> Result<()> {
-        Err(Error::ExpectedString)
+        Err(Error::new(
+            ErrorKind::ExpectedString,
+            Some(self.de.tokens.index),
+        ))
     }
 
-    // Newtype variants are represented in JSON as `{ NAME: VALUE }` so
+    // Newtype variants are represented as `d<NAME><VALUE>e` so
     // deserialize the value here.
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
@@ -548,7 +958,7 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
         seed.deserialize(self.de)
     }
 
-    // Tuple variants are represented in JSON as `{ NAME: [DATA...] }` so
+    // Tuple variants are represented as `d<NAME>l<DATA...>ee` so
     // deserialize the sequence of data here.
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
@@ -557,7 +967,7 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
         de::Deserializer::deserialize_seq(self.de, visitor)
     }
 
-    // Struct variants are represented in JSON as `{ NAME: { K: V, ... } }` so
+    // Struct variants are represented as `d<NAME>d<K><V>...ee` so
     // deserialize the inner map here.
     fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
@@ -583,7 +993,545 @@ fn test_struct() {
     let expected = Test {
         hello: "world".to_owned(),
         spam: vec!["a".to_owned(), "b".to_owned()],
-        bin: b"\xF0\x90\x80World",
+        bin: "\u{FFFD}World".as_bytes(),
     };
     assert_eq!(expected, from_str(j).unwrap());
 }
+
+#[test]
+fn test_struct_bytes() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Test<'a> {
+        hello: &'a str,
+        spam: Vec<String>,
+        #[serde(borrow)]
+        bin: &'a [u8],
+    }
+
+    let j = b"d5:hello5:world4:spaml1:a1:be3:bin8:\xF0\x90\x80Worlde";
+    let expected = Test {
+        hello: "world",
+        spam: vec!["a".to_owned(), "b".to_owned()],
+        bin: b"\xF0\x90\x80World",
+    };
+    assert_eq!(expected, from_bytes(j).unwrap());
+}
+
+#[test]
+fn test_invalid_utf8_string() {
+    assert_eq!(
+        from_bytes::<String>(b"3:\xF0\x90\x80").unwrap_err().kind(),
+        &ErrorKind::InvalidUtf8
+    );
+}
+
+#[test]
+fn test_torrent_fixtures() {
+    #[derive(Deserialize, Debug)]
+    struct Info<'a> {
+        name: &'a str,
+        #[serde(rename = "piece length")]
+        piece_length: u64,
+        #[serde(borrow, with = "serde_bytes")]
+        pieces: &'a [u8],
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Torrent<'a> {
+        announce: &'a str,
+        #[serde(borrow)]
+        info: Info<'a>,
+    }
+
+    let data = include_bytes!("../fixtures/single-file.torrent");
+    let torrent: Torrent = from_bytes(data).unwrap();
+    assert_eq!(torrent.announce, "http://tracker.example.org:6969/announce");
+    assert_eq!(torrent.info.name, "single.bin");
+    assert_eq!(torrent.info.piece_length, 16384);
+    assert_eq!(torrent.info.pieces.len(), 7 * 20);
+
+    let data = include_bytes!("../fixtures/multi-file.torrent");
+    let torrent: Torrent = from_bytes(data).unwrap();
+    assert_eq!(torrent.info.name, "multi");
+    assert_eq!(torrent.info.pieces.len(), 7 * 20);
+}
+
+#[test]
+fn test_integers() {
+    assert_eq!(from_bytes::<i64>(b"i0e").unwrap(), 0);
+    assert_eq!(from_bytes::<i64>(b"i42e").unwrap(), 42);
+    assert_eq!(from_bytes::<i64>(b"i-42e").unwrap(), -42);
+    assert_eq!(from_bytes::<u8>(b"i255e").unwrap(), 255);
+    assert_eq!(from_bytes::<i8>(b"i-128e").unwrap(), -128);
+    assert_eq!(
+        from_bytes::<i64>(b"i-9223372036854775808e").unwrap(),
+        i64::MIN
+    );
+    assert_eq!(
+        from_bytes::<u64>(b"i18446744073709551615e").unwrap(),
+        u64::MAX
+    );
+    assert_eq!(
+        from_bytes::<Vec<u32>>(b"li1ei22ei333ee").unwrap(),
+        vec![1, 22, 333]
+    );
+}
+
+#[test]
+fn test_invalid_integers() {
+    assert_eq!(
+        from_bytes::<i64>(b"i-0e").unwrap_err().kind(),
+        &ErrorKind::NegativeZero
+    );
+    assert_eq!(
+        from_bytes::<i64>(b"i03e").unwrap_err().kind(),
+        &ErrorKind::LeadingZero
+    );
+    assert_eq!(
+        from_bytes::<i64>(b"i-03e").unwrap_err().kind(),
+        &ErrorKind::LeadingZero
+    );
+    assert_eq!(
+        from_bytes::<i64>(b"ie").unwrap_err().kind(),
+        &ErrorKind::ExpectedInteger
+    );
+    assert_eq!(
+        from_bytes::<i64>(b"i-e").unwrap_err().kind(),
+        &ErrorKind::ExpectedInteger
+    );
+    assert_eq!(
+        from_bytes::<i64>(b"i1x2e").unwrap_err().kind(),
+        &ErrorKind::ExpectedInteger
+    );
+    assert_eq!(
+        from_bytes::<i64>(b"i12").unwrap_err().kind(),
+        &ErrorKind::Eof
+    );
+}
+
+#[test]
+fn test_integer_overflow() {
+    assert_eq!(
+        from_bytes::<u8>(b"i256e").unwrap_err().kind(),
+        &ErrorKind::IntegerOverflow
+    );
+    assert_eq!(
+        from_bytes::<i8>(b"i-129e").unwrap_err().kind(),
+        &ErrorKind::IntegerOverflow
+    );
+    assert_eq!(
+        from_bytes::<u32>(b"i-1e").unwrap_err().kind(),
+        &ErrorKind::IntegerOverflow
+    );
+    assert_eq!(
+        from_bytes::<i64>(b"i9223372036854775808e")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::IntegerOverflow
+    );
+    assert_eq!(
+        from_bytes::<u64>(b"i18446744073709551616e")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::IntegerOverflow
+    );
+    assert_eq!(
+        from_bytes::<u64>(b"i1000000000000000000000000000000000000000000e")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::IntegerOverflow
+    );
+}
+
+#[test]
+fn test_long_strings() {
+    let long = "a".repeat(300);
+    let input = format!("300:{}", long);
+    assert_eq!(from_str::<String>(&input).unwrap(), long);
+    assert_eq!(
+        from_bytes::<&str>(b"10:short").unwrap_err().kind(),
+        &ErrorKind::Eof
+    );
+}
+
+#[test]
+fn test_modes() {
+    use crate::Value;
+
+    let strict = Options::strict();
+    let lenient = Options::lenient();
+
+    assert_eq!(
+        strict
+            .from_bytes::<Value>(b"d1:bi1e1:ai2ee")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::UnsortedKey
+    );
+    assert_eq!(
+        strict
+            .from_bytes::<Value>(b"d1:ai1e1:ai2ee")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::DuplicateKey
+    );
+    assert_eq!(
+        strict.from_bytes::<&str>(b"04:spam").unwrap_err().kind(),
+        &ErrorKind::LeadingZero
+    );
+    assert_eq!(
+        strict.from_bytes::<i64>(b"i-0e").unwrap_err().kind(),
+        &ErrorKind::NegativeZero
+    );
+    assert_eq!(
+        strict.from_bytes::<i64>(b"i1ei2e").unwrap_err().kind(),
+        &ErrorKind::TrailingCharacters
+    );
+    assert_eq!(strict.from_bytes::<&str>(b"0:").unwrap(), "");
+
+    assert!(from_bytes::<Value>(b"d1:bi1e1:ai2ee").is_ok());
+    assert_eq!(from_bytes::<&str>(b"04:spam").unwrap(), "spam");
+    assert_eq!(
+        from_bytes::<i64>(b"i007e").unwrap_err().kind(),
+        &ErrorKind::LeadingZero
+    );
+
+    assert_eq!(lenient.from_bytes::<i64>(b"i007e").unwrap(), 7);
+    assert_eq!(lenient.from_bytes::<i64>(b"i-0e").unwrap(), 0);
+    assert_eq!(lenient.from_bytes::<i64>(b"i1ei2e").unwrap(), 1);
+}
+
+#[test]
+fn test_strict_nested_dicts() {
+    #[derive(Deserialize, Debug)]
+    struct Torrent {
+        #[allow(dead_code)]
+        announce: String,
+    }
+
+    let input = b"d8:announce3:url4:infod4:name1:a6:lengthi1eee";
+    assert!(from_bytes::<Torrent>(input).is_ok());
+    let err = Options::strict().from_bytes::<Torrent>(input).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::UnsortedKey);
+    assert_eq!(err.offset(), Some(32));
+    assert_eq!(err.path().as_deref(), Some("info"));
+}
+
+#[test]
+fn test_strict_torrent_fixtures() {
+    use crate::Value;
+
+    let strict = Options::strict();
+    assert!(strict
+        .from_bytes::<Value>(include_bytes!("../fixtures/single-file.torrent"))
+        .is_ok());
+    assert!(strict
+        .from_bytes::<Value>(include_bytes!("../fixtures/multi-file.torrent"))
+        .is_ok());
+}
+
+#[test]
+fn test_byte_and_integer_keys() {
+    use std::collections::BTreeMap;
+
+    let m: BTreeMap<Vec<u8>, u8> = from_bytes(b"d11:ut_metadatai1e6:ut_pexi2ee").unwrap();
+    assert_eq!(m[&b"ut_pex"[..]], 2);
+    #[cfg(feature = "std")]
+    {
+        let layers: std::collections::HashMap<serde_bytes::ByteBuf, &str> =
+            from_bytes(b"d2:\x00\x001:a2:\xff\xff1:be").unwrap();
+        assert_eq!(layers[&serde_bytes::ByteBuf::from(vec![0xff; 2])], "b");
+    }
+    let hashes: BTreeMap<[u8; 3], i64> = from_bytes(b"d3:\xab\xab\xabi1ee").unwrap();
+    assert_eq!(hashes[&[0xab; 3]], 1);
+    let pieces: BTreeMap<u32, &str> = from_bytes(b"d2:101:a1:21:be").unwrap();
+    assert_eq!(pieces[&10], "a");
+    let signed: BTreeMap<i8, u8> = from_bytes(b"d2:-1i1ee").unwrap();
+    assert_eq!(signed[&-1], 1);
+
+    for (input, kind) in [
+        (&b"d2:01i1ee"[..], ErrorKind::LeadingZero),
+        (b"d2:-0i1ee", ErrorKind::NegativeZero),
+        (b"d1:xi1ee", ErrorKind::ExpectedInteger),
+        (b"d2:+1i1ee", ErrorKind::ExpectedInteger),
+        (b"d3:256i1ee", ErrorKind::IntegerOverflow),
+    ] {
+        let err = from_bytes::<BTreeMap<u8, u8>>(input).unwrap_err();
+        assert_eq!(err.kind(), &kind);
+        assert_eq!(err.offset(), Some(1));
+    }
+    let err = from_bytes::<BTreeMap<[u8; 2], u8>>(b"d3:abci1ee").unwrap_err();
+    assert_eq!(err.offset(), Some(1));
+    // Integer keys follow the mode, like integer values.
+    let lenient = Options::new().mode(Mode::Lenient);
+    let pieces: BTreeMap<u8, u8> = lenient.from_bytes(b"d2:01i1e2:-0i2ee").unwrap();
+    assert_eq!(pieces[&1], 1);
+    assert_eq!(pieces[&0], 2);
+    let strict = Options::new().mode(Mode::Strict);
+    assert_eq!(
+        strict
+            .from_bytes::<BTreeMap<u8, u8>>(b"d2:01i1ee")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::LeadingZero
+    );
+}
+
+#[test]
+fn test_keys_must_be_strings() {
+    assert_eq!(
+        from_bytes::<crate::Value>(b"di1ei2ee").unwrap_err().kind(),
+        &ErrorKind::KeyMustBeString
+    );
+}
+
+#[test]
+fn test_error_position() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct File {
+        length: u64,
+        path: Vec<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Info {
+        files: Vec<File>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Torrent {
+        info: Info,
+    }
+
+    // The second file has a negative length.
+    let input = b"d4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi-1e4:pathl1:beeeee";
+    let err = from_bytes::<Torrent>(input).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::IntegerOverflow);
+    assert_eq!(err.offset(), Some(49));
+    assert_eq!(err.path().as_deref(), Some("info.files[1].length"));
+    assert_eq!(
+        err.to_string(),
+        "integer out of range at `info.files[1].length` (byte offset 49)"
+    );
+
+    let input = b"d4:infod5:filesld6:lengthi1e4:path1:aeeee";
+    let err = from_bytes::<Torrent>(input).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::ExpectedArray);
+    assert_eq!(err.offset(), Some(34));
+    assert_eq!(err.path().as_deref(), Some("info.files[0].path"));
+
+    // Errors raised by the `Deserialize` impl point at the offending value.
+    let input = b"d4:infod5:filesld6:lengthi1eeeee";
+    let err = from_bytes::<Torrent>(input).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::Message("missing field `path`".to_owned())
+    );
+    assert_eq!(err.offset(), Some(16));
+    assert_eq!(err.path().as_deref(), Some("info.files[0]"));
+
+    let err = from_bytes::<Torrent>(b"d4:infod5:filesle").unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Eof);
+    assert_eq!(err.offset(), Some(17));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_from_reader() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Torrent {
+        announce: String,
+        info: crate::Value,
+    }
+
+    let file = std::fs::File::open("fixtures/single-file.torrent").unwrap();
+    let torrent: Torrent = from_reader(file).unwrap();
+    assert_eq!(torrent.announce, "http://tracker.example.org:6969/announce");
+    assert_eq!(torrent.info["name"].as_str(), Some("single.bin"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_from_reader_io_error() {
+    struct Broken;
+
+    impl io::Read for Broken {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+        }
+    }
+
+    let err = from_reader::<_, crate::Value>(Broken).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Io(io::ErrorKind::ConnectionReset));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_from_reader_stream() {
+    // A reader handing out a byte at a time, like a slow connection.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(1);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    let mut input: &[u8] = b"d3:cow3:mooeli1e5:abcdeei7e";
+    let first: crate::Value = from_reader(&mut input).unwrap();
+    assert_eq!(first["cow"].as_str(), Some("moo"));
+    assert_eq!(input, b"li1e5:abcdeei7e");
+    let second: (i64, String) = from_reader(&mut input).unwrap();
+    assert_eq!(second, (1, "abcde".to_owned()));
+    assert_eq!(input, b"i7e");
+
+    let mut stream = Trickle(b"5:hello3:bye");
+    assert_eq!(from_reader::<_, String>(&mut stream).unwrap(), "hello");
+    assert_eq!(from_reader::<_, String>(&mut stream).unwrap(), "bye");
+    let err = from_reader::<_, String>(&mut stream).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Eof);
+}
+
+#[test]
+fn test_data_model() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Unit;
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Event {
+        Started,
+        Completed(u64),
+        Range(u32, u32),
+        Stopped { uploaded: u64, downloaded: u64 },
+    }
+
+    assert!(from_bytes::<bool>(b"i1e").unwrap());
+    assert!(!from_bytes::<bool>(b"i0e").unwrap());
+    assert_eq!(
+        from_bytes::<bool>(b"i2e").unwrap_err().kind(),
+        &ErrorKind::ExpectedBoolean
+    );
+    assert_eq!(from_bytes::<char>("2:\u{e9}".as_bytes()).unwrap(), '\u{e9}');
+    assert!(from_bytes::<char>(b"2:ab").is_err());
+    from_bytes::<()>(b"0:").unwrap();
+    assert_eq!(from_bytes::<Unit>(b"0:").unwrap(), Unit);
+    assert!(from_bytes::<()>(b"1:a").is_err());
+    assert_eq!(from_bytes::<Option<u8>>(b"i3e").unwrap(), Some(3));
+    assert_eq!(
+        from_bytes::<serde_bytes::ByteBuf>(b"1:\xff").unwrap(),
+        serde_bytes::ByteBuf::from(vec![0xff])
+    );
+    assert_eq!(from_bytes::<Event>(b"7:Started").unwrap(), Event::Started);
+    assert_eq!(
+        from_bytes::<Event>(b"d9:Completedi5ee").unwrap(),
+        Event::Completed(5)
+    );
+    assert_eq!(
+        from_bytes::<Event>(b"d5:Rangeli1ei2eee").unwrap(),
+        Event::Range(1, 2)
+    );
+    assert_eq!(
+        from_bytes::<Event>(b"d7:Stoppedd10:downloadedi2e8:uploadedi1eee").unwrap(),
+        Event::Stopped {
+            uploaded: 1,
+            downloaded: 2
+        }
+    );
+    assert_eq!(
+        from_bytes::<Event>(b"i1e").unwrap_err().kind(),
+        &ErrorKind::ExpectedEnum
+    );
+    assert_eq!(
+        from_bytes::<Event>(b"d9:Completedi5e1:xi1ee")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::ExpectedMapEnd
+    );
+    assert_eq!(
+        from_bytes::<f64>(b"i1e").unwrap_err().kind(),
+        &ErrorKind::UnsupportedType
+    );
+}
+
+#[test]
+fn test_optional_fields() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Response {
+        interval: u32,
+        #[serde(rename = "min interval")]
+        min_interval: Option<u32>,
+        #[serde(rename = "tracker id")]
+        tracker_id: Option<String>,
+        #[serde(default)]
+        complete: u32,
+    }
+
+    let response: Response = from_bytes(b"d8:intervali1800e10:tracker id3:abce").unwrap();
+    assert_eq!(
+        response,
+        Response {
+            interval: 1800,
+            min_interval: None,
+            tracker_id: Some("abc".to_owned()),
+            complete: 0,
+        }
+    );
+}
+
+#[test]
+fn test_limits() {
+    use crate::Value;
+
+    let nested = format!("{}{}", "l".repeat(300), "e".repeat(300));
+    let err = from_bytes::<Value>(nested.as_bytes()).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::DepthLimitExceeded);
+    assert_eq!(err.offset(), Some(256));
+    let options = Options::new().max_depth(300);
+    assert!(options.from_bytes::<Value>(nested.as_bytes()).is_ok());
+    assert_eq!(
+        Options::new()
+            .max_depth(1)
+            .from_bytes::<de::IgnoredAny>(b"d1:ali1eee")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::DepthLimitExceeded
+    );
+
+    let options = Options::new().max_string_length(4);
+    assert_eq!(options.from_bytes::<&str>(b"4:spam").unwrap(), "spam");
+    let err = options.from_bytes::<&str>(b"99999999999:spam").unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::StringTooLong);
+    assert_eq!(err.offset(), Some(0));
+
+    let options = Options::new().max_items(3);
+    assert!(options.from_bytes::<Value>(b"ld1:ai1eei2ee").is_ok());
+    assert_eq!(
+        options
+            .from_bytes::<Value>(b"ld1:ai1eei2ei3ee")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::TooManyItems
+    );
+
+    let options = Options::new().max_input_size(4);
+    assert_eq!(options.from_bytes::<i64>(b"i12e").unwrap(), 12);
+    assert_eq!(
+        options.from_bytes::<i64>(b"i123e").unwrap_err().kind(),
+        &ErrorKind::InputTooLarge
+    );
+    #[cfg(feature = "std")]
+    assert_eq!(
+        options
+            .from_reader::<_, i64>(io::repeat(b'1'))
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::InputTooLarge
+    );
+}
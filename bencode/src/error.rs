@@ -1,77 +1,280 @@
-use std;
-use std::fmt::{self, Display};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+#[cfg(feature = "std")]
+use std::io;
 
 use serde::{de, ser};
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
-// This is a bare-bones implementation. A real library would provide additional
-// information in its error type, for example the line and column at which the
-// error occurred, the byte offset into the input, or the current key being
-// processed.
+/// An error that occurred while serializing or deserializing bencode.
+///
+/// Besides the [`ErrorKind`], errors raised while decoding carry the byte
+/// offset into the input at which they occurred and the path of dictionary
+/// keys and list indices leading to the offending value, for example
+/// `info.files[3].path`.
 #[derive(Debug)]
-pub enum Error {
-    // One or more variants that can be created by data structures through the
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<usize>,
+    // Path segments from the innermost to the outermost, as they are pushed
+    // while the error propagates out of nested values.
+    path: Vec<Segment>,
+    // The underlying error for `ErrorKind::Io`.
+    #[cfg(feature = "std")]
+    io: Option<io::Error>,
+}
 
+/// The category of an [`Error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    // One or more variants that can be created by data structures through the
     // `ser::Error` and `de::Error` traits. For example the Serialize impl for
     // Mutex<T> might return an error because the mutex is poisoned, or the
     // Deserialize impl for a struct may return an error because a required
     // field is missing.
     Message(String),
 
+    // Reading from or writing to the underlying `io::Read` or `io::Write`
+    // failed. The original `io::Error` is available as the error's source.
+    #[cfg(feature = "std")]
+    Io(io::ErrorKind),
+
     // Zero or more variants that can be created directly by the Serializer and
     // Deserializer without going through `ser::Error` and `de::Error`. These
-    // are specific to the format, in this case JSON.
+    // are specific to the format, in this case bencode.
     Eof,
     Syntax,
     ExpectedBoolean,
     ExpectedInteger,
+    LeadingZero,
+    NegativeZero,
+    IntegerOverflow,
     ExpectedString,
-    ExpectedNull,
+    InvalidUtf8,
     ExpectedArray,
-    ExpectedArrayComma,
     ExpectedArrayEnd,
     ExpectedMap,
-    ExpectedMapColon,
-    ExpectedMapComma,
     ExpectedMapEnd,
     ExpectedEnum,
+    KeyMustBeString,
+    DuplicateKey,
+    UnsortedKey,
+    NoneNotInDict,
+    UnsupportedType,
     TrailingCharacters,
+    DepthLimitExceeded,
+    StringTooLong,
+    TooManyItems,
+    InputTooLarge,
+    InvalidPointer,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+impl Error {
+    pub(crate) fn new(kind: ErrorKind, offset: Option<usize>) -> Self {
+        Error {
+            kind,
+            offset,
+            path: Vec::new(),
+            #[cfg(feature = "std")]
+            io: None,
+        }
+    }
+
+    /// The category of the error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The byte offset into the input at which the error occurred, if known.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// The path to the value in which the error occurred, such as
+    /// `info.files[3].path`, or `None` if it occurred at the top level.
+    pub fn path(&self) -> Option<String> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let mut path = String::new();
+        for segment in self.path.iter().rev() {
+            match segment {
+                Segment::Key(key) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(&String::from_utf8_lossy(key));
+                }
+                Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        Some(path)
+    }
+
+    // Records the offset unless a more precise one is already known.
+    pub(crate) fn at(mut self, offset: usize) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    pub(crate) fn in_key(mut self, key: &[u8]) -> Self {
+        self.path.push(Segment::Key(key.to_vec()));
+        self
+    }
+
+    pub(crate) fn in_index(mut self, index: usize) -> Self {
+        self.path.push(Segment::Index(index));
+        self
+    }
+
+    /// Places an error raised while decoding a value on its own, such as
+    /// the bytes of a [`RawValue`](crate::RawValue), within the document the
+    /// value was taken from. `start` is the byte offset of the value in that
+    /// document and `key` the dictionary key it is stored under.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// #[derive(Debug, Deserialize)]
+    /// struct Info {
+    ///     length: u64,
+    /// }
+    ///
+    /// let input = b"d4:infod6:lengthi-1eee";
+    /// let err = bencode::from_bytes::<Info>(&input[7..]).unwrap_err();
+    /// assert_eq!(err.offset(), Some(9));
+    /// let err = err.within(7, b"info");
+    /// assert_eq!(err.offset(), Some(16));
+    /// assert_eq!(err.path().as_deref(), Some("info.length"));
+    /// ```
+    pub fn within(mut self, start: usize, key: &[u8]) -> Self {
+        self.offset = self.offset.map(|offset| offset + start);
+        self.in_key(key)
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error::new(kind, None)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        let mut error = Error::from(ErrorKind::Io(err.kind()));
+        error.io = Some(err);
+        error
+    }
 }
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        ErrorKind::Message(msg.to_string()).into()
     }
 }
 
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        ErrorKind::Message(msg.to_string()).into()
     }
 }
 
-impl Display for Error {
+impl Display for ErrorKind {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::Message(msg) => formatter.write_str(msg),
-            Error::Eof => formatter.write_str("unexpected end of input"),
-            Error::ExpectedString => formatter.write_str("expected string"),
-            Error::ExpectedBoolean => todo!(),
-            Error::ExpectedInteger => todo!(),
-            Error::ExpectedNull => todo!(),
-            Error::ExpectedArray => todo!(),
-            Error::ExpectedArrayComma => todo!(),
-            Error::ExpectedArrayEnd => todo!(),
-            Error::ExpectedMap => todo!(),
-            Error::ExpectedMapColon => todo!(),
-            Error::ExpectedMapComma => todo!(),
-            Error::ExpectedMapEnd => todo!(),
-            Error::ExpectedEnum => todo!(),
-            Error::TrailingCharacters => todo!(),
-            Error::Syntax => todo!(),
+            ErrorKind::Message(msg) => formatter.write_str(msg),
+            #[cfg(feature = "std")]
+            ErrorKind::Io(kind) => write!(formatter, "I/O error: {}", io::Error::from(*kind)),
+            ErrorKind::Eof => formatter.write_str("unexpected end of input"),
+            ErrorKind::Syntax => formatter.write_str("expected `i`, `l`, `d` or a digit"),
+            ErrorKind::ExpectedBoolean => formatter.write_str("expected boolean `i0e` or `i1e`"),
+            ErrorKind::ExpectedInteger => formatter.write_str("expected integer"),
+            ErrorKind::LeadingZero => formatter.write_str("number has a leading zero"),
+            ErrorKind::NegativeZero => formatter.write_str("negative zero is not a valid integer"),
+            ErrorKind::IntegerOverflow => formatter.write_str("integer out of range"),
+            ErrorKind::ExpectedString => formatter.write_str("expected string"),
+            ErrorKind::InvalidUtf8 => formatter.write_str("string is not valid UTF-8"),
+            ErrorKind::ExpectedArray => formatter.write_str("expected list"),
+            ErrorKind::ExpectedArrayEnd => formatter.write_str("expected end of list"),
+            ErrorKind::ExpectedMap => formatter.write_str("expected dictionary"),
+            ErrorKind::ExpectedMapEnd => formatter.write_str("expected end of dictionary"),
+            ErrorKind::ExpectedEnum => formatter.write_str("expected enum"),
+            ErrorKind::KeyMustBeString => formatter.write_str("dictionary key must be a string"),
+            ErrorKind::DuplicateKey => formatter.write_str("duplicate dictionary key"),
+            ErrorKind::UnsortedKey => formatter.write_str("dictionary keys are not sorted"),
+            ErrorKind::NoneNotInDict => {
+                formatter.write_str("`None` can only be serialized as a dictionary value")
+            }
+            ErrorKind::UnsupportedType => formatter.write_str("type is not supported by bencode"),
+            ErrorKind::TrailingCharacters => formatter.write_str("trailing data after value"),
+            ErrorKind::DepthLimitExceeded => formatter.write_str("nesting depth limit exceeded"),
+            ErrorKind::StringTooLong => formatter.write_str("string length limit exceeded"),
+            ErrorKind::TooManyItems => formatter.write_str("item count limit exceeded"),
+            ErrorKind::InputTooLarge => formatter.write_str("input size limit exceeded"),
+            ErrorKind::InvalidPointer => formatter.write_str("no such path in value"),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature = "std")]
+        if let Some(err) = &self.io {
+            write!(formatter, "I/O error: {}", err)?;
+        } else {
+            Display::fmt(&self.kind, formatter)?;
         }
+        #[cfg(not(feature = "std"))]
+        Display::fmt(&self.kind, formatter)?;
+        if let Some(path) = self.path() {
+            write!(formatter, " at `{}`", path)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(formatter, " (byte offset {})", offset)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io
+            .as_ref()
+            .map(|err| err as &(dyn std::error::Error + 'static))
     }
 }
 
-impl std::error::Error for Error {}
+// Serde requires its own stand-in for `std::error::Error` without `std`.
+#[cfg(not(feature = "std"))]
+impl de::StdError for Error {}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_display() {
+    let err = Error::new(ErrorKind::ExpectedInteger, Some(42))
+        .in_key(b"length")
+        .in_index(3)
+        .in_key(b"files")
+        .in_key(b"info");
+    assert_eq!(err.path().as_deref(), Some("info.files[3].length"));
+    assert_eq!(
+        err.to_string(),
+        "expected integer at `info.files[3].length` (byte offset 42)"
+    );
+
+    let err = Error::from(ErrorKind::Message("missing field `name`".to_owned()));
+    assert_eq!(err.path(), None);
+    assert_eq!(err.to_string(), "missing field `name`");
+}
@@ -0,0 +1,444 @@
+//! Decoding values from input that arrives in chunks.
+
+use alloc::vec::Vec;
+use serde::de::DeserializeOwned;
+
+use crate::de::Options;
+use crate::error::{Error, ErrorKind, Result};
+
+/// The outcome of feeding input to an [`IncrementalDecoder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// The buffered input ends in the middle of a value.
+    NeedMoreData,
+    /// A complete value has been buffered. It spans this many bytes from the
+    /// start of the buffer.
+    Complete(usize),
+}
+
+/// A decoder that is fed input in chunks, such as extension messages or
+/// tracker responses read off a socket, and decodes one value at a time.
+///
+/// Values are delimited as the input arrives, so each chunk is only looked
+/// at once, and then decoded with the [`Options`] the decoder was created
+/// with once complete.
+///
+/// ```
+/// # use bencode::{IncrementalDecoder, Progress, Value};
+/// let mut decoder = IncrementalDecoder::new();
+/// assert_eq!(decoder.feed(b"d3:cow3:m").unwrap(), Progress::NeedMoreData);
+/// assert_eq!(decoder.feed(b"ooei1e").unwrap(), Progress::Complete(12));
+/// let value: Value = decoder.decode().unwrap().unwrap();
+/// assert_eq!(value["cow"].as_str(), Some("moo"));
+/// assert_eq!(decoder.decode::<i64>().unwrap(), Some(1));
+/// ```
+#[derive(Debug, Default)]
+pub struct IncrementalDecoder {
+    options: Options,
+    buf: Vec<u8>,
+    scanner: Scanner,
+}
+
+impl IncrementalDecoder {
+    pub fn new() -> Self {
+        IncrementalDecoder::default()
+    }
+
+    pub fn with_options(options: Options) -> Self {
+        IncrementalDecoder {
+            options,
+            buf: Vec::new(),
+            scanner: Scanner::default(),
+        }
+    }
+
+    /// Appends `chunk` to the buffered input and reports whether it now
+    /// starts with a complete value.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Progress> {
+        self.buf.extend_from_slice(chunk);
+        self.scanner.scan(&self.buf, &self.options)
+    }
+
+    /// Removes the first value from the buffered input and decodes it, or
+    /// returns `None` if it is not complete yet.
+    pub fn decode<T>(&mut self) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        match self.scanner.scan(&self.buf, &self.options)? {
+            Progress::NeedMoreData => Ok(None),
+            Progress::Complete(len) => {
+                self.scanner = Scanner::default();
+                let value = self.options.from_bytes(&self.buf[..len]);
+                self.buf.drain(..len);
+                value.map(Some)
+            }
+        }
+    }
+
+    /// The input that has been fed but not decoded yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+// Finds where the value at the start of a growing buffer ends. Only the
+// framing is checked here, the full validation happens when the complete
+// value is decoded.
+#[derive(Debug, Default)]
+pub(crate) struct Scanner {
+    // Offset of the next token that has not been scanned completely.
+    offset: usize,
+    // The number of lists and dictionaries open at `offset`.
+    depth: usize,
+    // After `NeedMoreData`, the fewest bytes that can complete the value.
+    missing: usize,
+    // Digits of the token at `offset` already scanned, so that they are not
+    // scanned again however slowly they arrive, and the string length they
+    // add up to.
+    digits: usize,
+    len: usize,
+}
+
+impl Scanner {
+    pub(crate) fn scan(&mut self, buf: &[u8], options: &Options) -> Result<Progress> {
+        // The value was already complete on a previous call.
+        if self.offset > 0 && self.depth == 0 {
+            return Ok(Progress::Complete(self.offset));
+        }
+        loop {
+            let start = self.offset;
+            if start > options.max_input_size {
+                return Err(Error::new(ErrorKind::InputTooLarge, Some(start)));
+            }
+            let byte = match buf.get(start) {
+                Some(&byte) => byte,
+                None => return self.need_more_data(buf, options, 1),
+            };
+            match byte {
+                b'i' => loop {
+                    match buf.get(start + 1 + self.digits) {
+                        Some(b'-' | b'0'..=b'9') => self.digits += 1,
+                        Some(b'e') => {
+                            self.offset += self.digits + 2;
+                            self.digits = 0;
+                            break;
+                        }
+                        Some(_) => {
+                            return Err(Error::new(
+                                ErrorKind::ExpectedInteger,
+                                Some(start + 1 + self.digits),
+                            ))
+                        }
+                        None => return self.need_more_data(buf, options, 1),
+                    }
+                },
+                b'0'..=b'9' => {
+                    // Zero-padded lengths are only rejected when the value is
+                    // decoded, so the digits are bounded by the size limit
+                    // rather than by the width of a `usize`.
+                    let colon = loop {
+                        match buf.get(start + self.digits) {
+                            Some(&ch @ b'0'..=b'9') => {
+                                self.len = self
+                                    .len
+                                    .checked_mul(10)
+                                    .and_then(|len| len.checked_add(usize::from(ch - b'0')))
+                                    .ok_or_else(|| {
+                                        Error::new(ErrorKind::IntegerOverflow, Some(start))
+                                    })?;
+                                self.digits += 1;
+                            }
+                            Some(b':') => break start + self.digits,
+                            Some(_) => {
+                                return Err(Error::new(
+                                    ErrorKind::ExpectedString,
+                                    Some(start + self.digits),
+                                ))
+                            }
+                            None => return self.need_more_data(buf, options, 1),
+                        }
+                    };
+                    if self.len > options.max_string_length {
+                        return Err(Error::new(ErrorKind::StringTooLong, Some(start)));
+                    }
+                    let end = (colon + 1)
+                        .checked_add(self.len)
+                        .ok_or_else(|| Error::new(ErrorKind::IntegerOverflow, Some(start)))?;
+                    if end > options.max_input_size {
+                        return Err(Error::new(ErrorKind::InputTooLarge, Some(start)));
+                    }
+                    if end > buf.len() {
+                        return self.need_more_data(buf, options, end - buf.len());
+                    }
+                    self.offset = end;
+                    self.digits = 0;
+                    self.len = 0;
+                }
+                b'l' | b'd' => {
+                    if self.depth >= options.max_depth {
+                        return Err(Error::new(ErrorKind::DepthLimitExceeded, Some(start)));
+                    }
+                    self.depth += 1;
+                    self.offset += 1;
+                    continue;
+                }
+                b'e' if self.depth > 0 => {
+                    self.depth -= 1;
+                    self.offset += 1;
+                }
+                _ => return Err(Error::new(ErrorKind::Syntax, Some(start))),
+            }
+            if self.depth == 0 {
+                return Ok(Progress::Complete(self.offset));
+            }
+        }
+    }
+
+    // The fewest bytes that can complete the value after `scan` returned
+    // `NeedMoreData`. Reading no more than this never reads past its end.
+    #[cfg(feature = "std")]
+    pub(crate) fn missing(&self) -> usize {
+        self.missing
+    }
+
+    // Waiting for more input is only allowed while the buffer is within the
+    // size limit, so that a peer cannot make it grow without end. The current
+    // token needs at least `token` more bytes, and each open container an `e`.
+    fn need_more_data(&mut self, buf: &[u8], options: &Options, token: usize) -> Result<Progress> {
+        if buf.len() > options.max_input_size {
+            return Err(Error::new(
+                ErrorKind::InputTooLarge,
+                Some(options.max_input_size),
+            ));
+        }
+        self.missing = token + self.depth;
+        Ok(Progress::NeedMoreData)
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::codec::ValueCodec;
+
+// The `tokio_util` integration, for reading values off an `AsyncRead`.
+#[cfg(feature = "tokio")]
+mod codec {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+
+    use super::{Progress, Scanner};
+    use crate::de::Options;
+    use crate::error::Error;
+    use crate::value::Value;
+
+    /// A [`Decoder`] that splits a byte stream into consecutive bencode
+    /// values, for use with `tokio_util::codec::FramedRead`.
+    #[derive(Debug)]
+    pub struct ValueCodec {
+        options: Options,
+        scanner: Scanner,
+    }
+
+    impl Default for ValueCodec {
+        fn default() -> Self {
+            ValueCodec::with_options(Options::new().max_input_size(Self::DEFAULT_MAX_FRAME_SIZE))
+        }
+    }
+
+    impl ValueCodec {
+        /// The largest value [`ValueCodec::new`] accepts, 16 MiB.
+        pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 << 20;
+
+        /// Creates a codec that rejects values larger than
+        /// [`ValueCodec::DEFAULT_MAX_FRAME_SIZE`], since the data usually
+        /// comes from a peer.
+        pub fn new() -> Self {
+            ValueCodec::default()
+        }
+
+        pub fn with_options(options: Options) -> Self {
+            ValueCodec {
+                options,
+                scanner: Scanner::default(),
+            }
+        }
+    }
+
+    impl Decoder for ValueCodec {
+        type Item = Value;
+        type Error = Error;
+
+        fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Value>, Error> {
+            match self.scanner.scan(src, &self.options)? {
+                Progress::NeedMoreData => Ok(None),
+                Progress::Complete(len) => {
+                    self.scanner = Scanner::default();
+                    let frame = src.split_to(len);
+                    self.options.from_bytes(&frame).map(Some)
+                }
+            }
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+
+    #[cfg(test)]
+    #[tokio::test]
+    async fn test_framed_read() {
+        use futures_util::StreamExt;
+        use tokio_util::codec::FramedRead;
+
+        let input: &[u8] = b"d1:ai1eeli2e3:abcei-3e";
+        let mut frames = FramedRead::with_capacity(input, ValueCodec::new(), 3);
+        assert_eq!(frames.next().await.unwrap().unwrap()["a"], Value::Int(1));
+        assert_eq!(
+            frames.next().await.unwrap().unwrap(),
+            Value::List(vec![Value::Int(2), Value::from("abc")])
+        );
+        assert_eq!(frames.next().await.unwrap().unwrap(), Value::Int(-3));
+        assert!(frames.next().await.is_none());
+    }
+
+    #[cfg(test)]
+    #[tokio::test]
+    async fn test_framed_read_too_large() {
+        use futures_util::StreamExt;
+        use tokio_util::codec::FramedRead;
+
+        // A peer announcing a string larger than the default limit.
+        let input: &[u8] = b"99999999:";
+        let mut frames = FramedRead::new(input, ValueCodec::new());
+        let err = frames.next().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), &crate::ErrorKind::InputTooLarge);
+    }
+
+    #[cfg(test)]
+    #[tokio::test]
+    async fn test_framed_read_truncated() {
+        use futures_util::StreamExt;
+        use tokio_util::codec::FramedRead;
+
+        let input: &[u8] = b"i1eli2e";
+        let mut frames = FramedRead::new(input, ValueCodec::new());
+        assert_eq!(frames.next().await.unwrap().unwrap(), Value::Int(1));
+        assert!(frames.next().await.unwrap().is_err());
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_byte_by_byte() {
+    let data = include_bytes!("../fixtures/single-file.torrent");
+    let mut decoder = IncrementalDecoder::new();
+    for (i, byte) in data.iter().enumerate() {
+        let progress = decoder.feed(&[*byte]).unwrap();
+        if i + 1 < data.len() {
+            assert_eq!(progress, Progress::NeedMoreData);
+            assert!(decoder.decode::<crate::Value>().unwrap().is_none());
+        } else {
+            assert_eq!(progress, Progress::Complete(data.len()));
+        }
+    }
+    let value: crate::Value = decoder.decode().unwrap().unwrap();
+    assert_eq!(value["info"]["name"].as_str(), Some("single.bin"));
+    assert!(decoder.buffered().is_empty());
+}
+
+#[test]
+fn test_scanner_errors() {
+    let mut decoder = IncrementalDecoder::new();
+    assert_eq!(
+        decoder.feed(b"li1xe").unwrap_err().kind(),
+        &ErrorKind::ExpectedInteger
+    );
+
+    let mut decoder = IncrementalDecoder::new();
+    assert_eq!(decoder.feed(b"e").unwrap_err().kind(), &ErrorKind::Syntax);
+
+    // Framing is checked as the input arrives, the rest once it is decoded.
+    let mut decoder = IncrementalDecoder::new();
+    assert_eq!(decoder.feed(b"i03e").unwrap(), Progress::Complete(4));
+    assert_eq!(
+        decoder.decode::<i64>().unwrap_err().kind(),
+        &ErrorKind::LeadingZero
+    );
+
+    let mut decoder = IncrementalDecoder::with_options(Options::new().max_string_length(10));
+    assert_eq!(
+        decoder.feed(b"99999999999:").unwrap_err().kind(),
+        &ErrorKind::StringTooLong
+    );
+
+    let mut decoder = IncrementalDecoder::with_options(Options::new().max_depth(2));
+    assert_eq!(
+        decoder.feed(b"lll").unwrap_err().kind(),
+        &ErrorKind::DepthLimitExceeded
+    );
+}
+
+#[test]
+fn test_long_digits() {
+    use crate::{Mode, Value};
+
+    // Zero-padded string lengths are only rejected in strict mode and
+    // integers beyond 20 characters are fine with leading zeros in lenient
+    // mode, so the scanner accepts any number of digits.
+    let padded = b"0000000000000000000000000004:spam";
+    let mut decoder = IncrementalDecoder::new();
+    for &byte in padded.iter() {
+        decoder.feed(&[byte]).unwrap();
+    }
+    assert_eq!(
+        decoder.decode::<Value>().unwrap(),
+        Some(Value::from("spam"))
+    );
+
+    let long = b"i-0000000000000000000000000042e";
+    let mut decoder = IncrementalDecoder::with_options(Options::new().mode(Mode::Lenient));
+    assert_eq!(decoder.feed(long).unwrap(), Progress::Complete(long.len()));
+    assert_eq!(decoder.decode::<i64>().unwrap(), Some(-42));
+
+    let mut decoder = IncrementalDecoder::with_options(Options::new().mode(Mode::Strict));
+    assert_eq!(
+        decoder.feed(padded).unwrap(),
+        Progress::Complete(padded.len())
+    );
+    assert_eq!(
+        decoder.decode::<Value>().unwrap_err().kind(),
+        &ErrorKind::LeadingZero
+    );
+
+    // A length that does not fit in a `usize` cannot be framed.
+    let mut decoder = IncrementalDecoder::new();
+    assert_eq!(
+        decoder
+            .feed(b"99999999999999999999999:")
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::IntegerOverflow
+    );
+}
+
+#[test]
+fn test_endless_digits() {
+    // Endless digits are bounded by the size limit.
+    let mut decoder = IncrementalDecoder::with_options(Options::new().max_input_size(64));
+    assert_eq!(decoder.feed(b"i").unwrap(), Progress::NeedMoreData);
+    let err = (0..100).find_map(|_| decoder.feed(b"1111").err()).unwrap();
+    assert_eq!(err.kind(), &ErrorKind::InputTooLarge);
+    assert!(decoder.buffered().len() <= 64 + 4);
+
+    let mut decoder = IncrementalDecoder::with_options(Options::new().max_input_size(64));
+    let err = (0..100).find_map(|_| decoder.feed(b"0000").err()).unwrap();
+    assert_eq!(err.kind(), &ErrorKind::InputTooLarge);
+
+    // The buffer cannot grow past the size limit while waiting.
+    let mut decoder = IncrementalDecoder::with_options(Options::new().max_input_size(8));
+    assert_eq!(decoder.feed(b"li1e").unwrap(), Progress::NeedMoreData);
+    assert_eq!(decoder.feed(b"i1").unwrap(), Progress::NeedMoreData);
+    assert_eq!(
+        decoder.feed(b"111").unwrap_err().kind(),
+        &ErrorKind::InputTooLarge
+    );
+}
@@ -0,0 +1,56 @@
+//! The part of `std::io` that the serializer writes through, with a
+//! replacement for `no_std` builds that can only write into a `Vec<u8>`.
+
+#[cfg(feature = "std")]
+pub use std::io::Write;
+
+#[cfg(not(feature = "std"))]
+pub use self::alloc_only::Write;
+
+#[cfg(not(feature = "std"))]
+mod alloc_only {
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    /// Writing into memory cannot fail, so there are no I/O errors.
+    #[derive(Debug)]
+    pub enum Error {}
+
+    impl From<Error> for crate::Error {
+        fn from(err: Error) -> Self {
+            match err {}
+        }
+    }
+
+    /// A sink for bytes, implemented for `Vec<u8>`.
+    pub trait Write {
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Error>;
+
+        fn write_fmt(&mut self, args: fmt::Arguments) -> Result<(), Error> {
+            struct Adapter<'a, W: ?Sized>(&'a mut W);
+
+            impl<W: Write + ?Sized> fmt::Write for Adapter<'_, W> {
+                fn write_str(&mut self, s: &str) -> fmt::Result {
+                    self.0.write_all(s.as_bytes()).map_err(|err| match err {})
+                }
+            }
+
+            fmt::write(&mut Adapter(self), args)
+                .expect("a formatting trait implementation returned an error");
+            Ok(())
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+            (**self).write_all(buf)
+        }
+    }
+}
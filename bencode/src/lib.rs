@@ -1,7 +1,70 @@
+//! Serde support for bencode, the encoding used by BitTorrent.
+//!
+//! Bencode only has integers, byte strings, lists and dictionaries, so the
+//! rest of the Serde data model is mapped onto them:
+//!
+//! | Rust                              | Bencode                              |
+//! |-----------------------------------|--------------------------------------|
+//! | integers                          | `i42e`                               |
+//! | `bool`                            | `i0e` or `i1e`                       |
+//! | `str`, `String`, `char`           | `4:spam`                             |
+//! | bytes (`serde_bytes`, `ByteBuf`)  | `4:spam`                             |
+//! | `()` and unit structs             | `0:`                                 |
+//! | `Some(value)`                     | `value`                              |
+//! | `None`                            | the dictionary entry is left out     |
+//! | sequences, tuples, tuple structs  | `l...e`                              |
+//! | maps and structs                  | `d...e`, keys sorted as raw bytes    |
+//! | unit variants                     | `7:Started`                          |
+//! | other enum variants               | `d7:Stopped...e` around the contents |
+//!
+//! `None` has no encoding of its own, so it is an error anywhere but as a
+//! dictionary value; missing `Option` fields deserialize as `None`. Floats
+//! are not supported.
+//!
+//! Map keys can be strings, byte strings such as `Vec<u8>`, `ByteBuf` or
+//! `[u8; 20]`, integers, which are written in decimal, and unit variants.
+//! Any other key is an [`ErrorKind::KeyMustBeString`] error.
+//!
+//! # Features
+//!
+//! - `std` (default): [`from_reader`], [`to_writer`] and `std::error::Error`
+//!   for [`Error`]. Without it the crate is `no_std` and only needs `alloc`.
+//! - `json`: lossless conversion between [`Value`] and `serde_json::Value`.
+//! - `tokio`: `ValueCodec`, a `tokio_util` decoder for byte streams.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[macro_use]
+mod macros;
+
 mod de;
 mod error;
+mod incremental;
+mod io;
+mod raw;
 mod ser;
+mod token;
+mod value;
 
-pub use de::{from_str, Deserializer};
-pub use error::{Error, Result};
+#[cfg(feature = "std")]
+pub use de::from_reader;
+pub use de::{from_bytes, from_str, Deserializer, Mode, Options};
+pub use error::{Error, ErrorKind, Result};
+#[cfg(feature = "tokio")]
+pub use incremental::ValueCodec;
+pub use incremental::{IncrementalDecoder, Progress};
+pub use raw::RawValue;
+#[cfg(feature = "std")]
+pub use ser::to_writer;
 pub use ser::{to_bytes, Serializer};
+pub use token::{Token, Tokenizer};
+pub use value::{from_value, to_value, Value, ValueRef};
+
+// Items used by the expansion of `bencode!`.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::collections::BTreeMap;
+    pub use alloc::vec;
+}
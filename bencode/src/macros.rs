@@ -0,0 +1,78 @@
+/// Builds a [`Value`](crate::Value) from a literal description.
+///
+/// Dictionaries are written as `key => value` pairs, either in braces or
+/// bare at the top level, and lists in brackets. Keys are anything that
+/// converts to bytes, such as `"name"` or `b"\xff"`. Values are nested
+/// dictionaries and lists, or single expressions converted with
+/// `Value::from`. Longer expressions need parentheses. Integers that may not
+/// fit in an `i64`, such as `u64` and `usize`, have no `From` conversion, so
+/// convert them with `Value::try_from` first.
+///
+/// ```
+/// # use bencode::{bencode, Value};
+/// let length = 16384;
+/// let value = bencode! {
+///     "announce" => "http://tracker.example.org:6969/announce",
+///     "info" => {
+///         "name" => "a.txt",
+///         "piece length" => length,
+///         "length" => (length * 2),
+///         "path" => ["dir", "a.txt"],
+///     },
+/// };
+/// assert_eq!(value["info"]["piece length"], Value::Int(16384));
+/// assert_eq!(
+///     bencode::to_bytes(&value["info"]["path"]).unwrap(),
+///     b"l3:dir5:a.txte"
+/// );
+/// ```
+#[macro_export]
+macro_rules! bencode {
+    ([ $($element:tt),* $(,)? ]) => {
+        $crate::Value::List($crate::__private::vec![$($crate::bencode!($element)),*])
+    };
+    ({ $($key:expr => $value:tt),* $(,)? }) => {{
+        #[allow(unused_mut)]
+        let mut dict = $crate::__private::BTreeMap::new();
+        $(
+            dict.insert(
+                <[u8]>::to_vec(::core::convert::AsRef::<[u8]>::as_ref($key)),
+                $crate::bencode!($value),
+            );
+        )*
+        $crate::Value::Dict(dict)
+    }};
+    ($($key:expr => $value:tt),+ $(,)?) => {
+        $crate::bencode!({ $($key => $value),+ })
+    };
+    ($value:expr) => {
+        $crate::Value::from($value)
+    };
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_bencode_macro() {
+    use crate::Value;
+
+    let value = bencode! {
+        "interval" => 1800,
+        "peers" => [
+            { "ip" => "10.0.0.1", "port" => 6881 },
+            { "ip" => "10.0.0.2", "port" => 6882, },
+        ],
+        b"\xff" => b"\x00\x01",
+        "empty" => {},
+        "list" => [],
+    };
+    assert_eq!(
+        crate::to_bytes(&value).unwrap(),
+        &b"d5:emptyde8:intervali1800e4:listle5:peersld2:ip8:10.0.0.14:porti6881eed2:ip8:10.0.0.24:porti6882eee1:\xff2:\x00\x01e"[..]
+    );
+    assert_eq!(bencode!("spam"), Value::from("spam"));
+    assert_eq!(
+        bencode!([1, [true]]),
+        Value::List(vec![Value::Int(1), Value::List(vec![Value::Int(1)])])
+    );
+}
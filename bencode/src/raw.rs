@@ -0,0 +1,174 @@
+//! Access to the exact encoded bytes of a value.
+
+use alloc::string::String;
+use core::fmt;
+
+use serde::de::{self, Visitor};
+use serde::{ser, Deserialize, Serialize};
+use serde_bytes::Bytes;
+
+use crate::de::Deserializer;
+use crate::error::Result;
+
+// The newtype struct name used to recognise a `RawValue` in the serializer
+// and deserializer. It cannot collide with a Rust identifier.
+pub(crate) const TOKEN: &str = "$bencode::private::RawValue";
+
+/// A value borrowed exactly as it appeared in the input.
+///
+/// When used as a field type, deserialization records the byte span of the
+/// field's value instead of decoding it, so the original encoding is kept
+/// untouched, including keys that the typed representation would drop. This
+/// is what the info hash of a torrent has to be computed over:
+///
+/// ```
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Metainfo<'a> {
+///     #[serde(borrow)]
+///     info: bencode::RawValue<'a>,
+/// }
+///
+/// let metainfo: Metainfo = bencode::from_bytes(b"d4:infod1:bi1e1:ai2eee").unwrap();
+/// assert_eq!(metainfo.info.as_bytes(), b"d1:bi1e1:ai2ee");
+/// ```
+///
+/// Serializing a `RawValue` writes the bytes back verbatim.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawValue<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> RawValue<'a> {
+    /// Wraps `bytes` after checking that they hold exactly one valid value.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        let mut deserializer = Deserializer::from_bytes(bytes);
+        de::IgnoredAny::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(RawValue { bytes })
+    }
+
+    /// The encoded bytes of the value.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Decodes the value into a typed representation.
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        crate::from_bytes(self.bytes)
+    }
+}
+
+impl fmt::Debug for RawValue<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_tuple("RawValue")
+            .field(&String::from_utf8_lossy(self.bytes))
+            .finish()
+    }
+}
+
+impl Serialize for RawValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, Bytes::new(self.bytes))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValue<'a> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RawVisitor;
+
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = RawValue<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a bencode value borrowed from the input")
+            }
+
+            fn visit_borrowed_bytes<E>(
+                self,
+                bytes: &'de [u8],
+            ) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(RawValue { bytes })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, RawVisitor)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_raw_info() {
+    #[derive(Deserialize)]
+    struct Info<'a> {
+        name: &'a str,
+        #[serde(rename = "piece length")]
+        piece_length: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct Metainfo<'a> {
+        announce: &'a str,
+        #[serde(borrow)]
+        info: RawValue<'a>,
+    }
+
+    let data = include_bytes!("../fixtures/multi-file.torrent");
+    let metainfo: Metainfo = crate::from_bytes(data).unwrap();
+    assert_eq!(
+        metainfo.announce,
+        "http://tracker.example.org:6969/announce"
+    );
+
+    // The info dictionary is the last entry of the fixture, so its span runs
+    // from right after the `4:info` key to just before the final `e`.
+    let start = data.windows(6).position(|w| w == b"4:info").unwrap() + 6;
+    assert_eq!(metainfo.info.as_bytes(), &data[start..data.len() - 1]);
+
+    // Keys that the typed struct does not know about are kept.
+    let raw = metainfo.info.as_bytes();
+    assert!(raw.windows(9).any(|w| w == b"7:private"));
+    let info: Info = metainfo.info.deserialize().unwrap();
+    assert_eq!(info.name, "multi");
+    assert_eq!(info.piece_length, 16384);
+}
+
+#[test]
+fn test_raw_round_trip() {
+    #[derive(Deserialize, Serialize)]
+    struct Message<'a> {
+        #[serde(borrow)]
+        payload: RawValue<'a>,
+        version: u8,
+    }
+
+    // The payload is not canonical, and is written back exactly as received.
+    let input = b"d7:payloadd1:bi1e1:ai2ee7:versioni1ee";
+    let message: Message = crate::from_bytes(input).unwrap();
+    assert_eq!(crate::to_bytes(&message).unwrap(), input);
+    assert_eq!(
+        crate::to_value(&message).unwrap()["payload"]["a"],
+        crate::Value::Int(2)
+    );
+}
+
+#[test]
+fn test_raw_from_bytes() {
+    assert!(RawValue::from_bytes(b"li1ee").is_ok());
+    assert!(RawValue::from_bytes(b"li1e").is_err());
+    assert!(RawValue::from_bytes(b"i1ei2e").is_err());
+}
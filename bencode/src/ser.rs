@@ -1,23 +1,77 @@
-use std::char::from_digit;
-
-use serde::{ser, Serialize};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::BufWriter;
+
+use serde::ser::{self, Impossible};
+use serde::Serialize;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::io;
+use crate::raw;
+
+/// A serializer writing bencode into any `io::Write`.
+///
+/// Dictionaries are encoded into memory first to sort their keys, see
+/// [`to_writer`](crate::to_writer).
+pub struct Serializer<W> {
+    writer: W,
+}
 
-use crate::error::{Error, Result};
+impl<W> Serializer<W>
+where
+    W: io::Write,
+{
+    pub fn new(writer: W) -> Self {
+        Serializer { writer }
+    }
 
-pub struct Serializer {
-    output: Vec<u8>,
+    /// Unwraps the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
 }
 
 pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
 where
-    T: Serialize,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(Vec::new());
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+/// Serializes `value` into `writer`.
+///
+/// Integers, strings and lists are written as they are serialized. Each
+/// dictionary, derived structs included, is encoded into memory until it is
+/// complete, because canonical bencode requires its keys to be sorted and
+/// the entries may arrive in any order. A value whose top level is a
+/// dictionary, such as a torrent, is therefore held in memory in full
+/// before it is written.
+///
+/// Serde does not tell a serializer the field names of a struct up front,
+/// so a struct is buffered even when its fields are declared in sorted
+/// order and could in principle be streamed.
+///
+/// Output is buffered internally and flushed before returning, so there is
+/// no need to wrap `writer` in a `BufWriter`.
+#[cfg(feature = "std")]
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
 {
-    let mut serializer = Serializer { output: Vec::new() };
+    let mut serializer = Serializer::new(BufWriter::new(writer));
     value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    io::Write::flush(&mut serializer.into_inner())?;
+    Ok(())
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl<'a, W> ser::Serializer for &'a mut Serializer<W>
+where
+    W: io::Write,
+{
     type Ok = ();
 
     type Error = Error;
@@ -26,12 +80,13 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     type SerializeTuple = Self;
     type SerializeTupleStruct = Self;
     type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeMap = SerializeDict<'a, W>;
+    type SerializeStruct = SerializeDict<'a, W>;
+    type SerializeStructVariant = SerializeDict<'a, W>;
 
-    fn serialize_bool(self, _v: bool) -> Result<()> {
-        unimplemented!();
+    // Booleans are written as the integers `i0e` and `i1e`.
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.serialize_i64(i64::from(v))
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
@@ -47,7 +102,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.output.append(&mut format!("i{}e", v).into_bytes());
+        write!(self.writer, "i{}e", v)?;
         Ok(())
     }
 
@@ -64,7 +119,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.output.append(&mut format!("i{}e", v).into_bytes());
+        write!(self.writer, "i{}e", v)?;
         Ok(())
     }
 
@@ -72,80 +127,92 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.serialize_f64(f64::from(v))
     }
 
+    // Bencode has no floating point numbers.
     fn serialize_f64(self, _v: f64) -> Result<()> {
-        unimplemented!();
+        Err(ErrorKind::UnsupportedType.into())
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
-        self.serialize_str(&v.to_string())
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.output
-            .push(from_digit(v.len() as u32, 10).unwrap() as u8);
-        self.output.push(b':');
-        self.output.append(&mut v.to_string().into_bytes());
-        Ok(())
+        self.serialize_bytes(v.as_bytes())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.output
-            .push(from_digit(v.len() as u32, 10).unwrap() as u8);
-        self.output.push(b':');
-        self.output.append(&mut v.to_vec());
+        write!(self.writer, "{}:", v.len())?;
+        self.writer.write_all(v)?;
         Ok(())
     }
 
+    // Bencode has no null. `None` can only appear as a dictionary value, where
+    // `SerializeDict` leaves the whole entry out.
     fn serialize_none(self) -> Result<()> {
-        unimplemented!()
+        Err(ErrorKind::NoneNotInDict.into())
     }
 
-    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        value.serialize(self)
     }
 
+    // Unit is written as the empty string `0:`.
     fn serialize_unit(self) -> Result<()> {
-        unimplemented!()
+        self.serialize_bytes(b"")
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        unimplemented!()
+        self.serialize_unit()
     }
 
+    // Enums are externally tagged. A unit variant is written as its name.
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<()> {
-        unimplemented!()
+        self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
+    // Newtype structs are transparent, except for `RawValue` whose bytes are
+    // already encoded and are copied to the output as they are.
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        if name == raw::TOKEN {
+            let raw = value.serialize(KeySerializer)?;
+            self.writer.write_all(&raw)?;
+            return Ok(());
+        }
+        value.serialize(self)
     }
 
+    // Other variants are written as a dictionary with a single entry mapping
+    // the variant name to its contents, here `d<NAME><VALUE>e`.
     fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        self.writer.write_all(b"d")?;
+        self.serialize_str(variant)?;
+        value.serialize(&mut *self)?;
+        self.writer.write_all(b"e")?;
+        Ok(())
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.output.push(b'l');
+        self.writer.write_all(b"l")?;
         Ok(self)
     }
 
@@ -161,37 +228,57 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.serialize_seq(Some(len))
     }
 
+    // Tuple variants are written as `d<NAME>l<DATA...>ee`.
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        unimplemented!()
+        self.writer.write_all(b"d")?;
+        self.serialize_str(variant)?;
+        self.writer.write_all(b"l")?;
+        Ok(self)
     }
 
+    // Dictionary entries are buffered and only written out once the whole
+    // dictionary is known, so that they can be sorted by key.
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.output.push(b'd');
-        Ok(self)
+        Ok(SerializeDict {
+            ser: self,
+            variant: None,
+            entries: Vec::new(),
+            key: None,
+        })
     }
 
     fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
         self.serialize_map(Some(len))
     }
 
+    // Struct variants are written as `d<NAME>d<K><V>...ee`, with the fields
+    // sorted like those of any other dictionary.
     fn serialize_struct_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        unimplemented!()
+        Ok(SerializeDict {
+            ser: self,
+            variant: Some(variant),
+            entries: Vec::new(),
+            key: None,
+        })
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl<W> ser::SerializeSeq for &mut Serializer<W>
+where
+    W: io::Write,
+{
     type Ok = ();
     type Error = Error;
 
@@ -205,12 +292,15 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
 
     // Close the sequence.
     fn end(self) -> Result<()> {
-        self.output.push(b'e');
+        self.writer.write_all(b"e")?;
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl<W> ser::SerializeTuple for &mut Serializer<W>
+where
+    W: io::Write,
+{
     type Ok = ();
     type Error = Error;
 
@@ -222,12 +312,15 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
     }
 
     fn end(self) -> Result<()> {
-        self.output.push(b'e');
+        self.writer.write_all(b"e")?;
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl<W> ser::SerializeTupleStruct for &mut Serializer<W>
+where
+    W: io::Write,
+{
     type Ok = ();
     type Error = Error;
 
@@ -239,12 +332,15 @@ impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
     }
 
     fn end(self) -> Result<()> {
-        self.output.push(b'e');
+        self.writer.write_all(b"e")?;
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl<W> ser::SerializeTupleVariant for &mut Serializer<W>
+where
+    W: io::Write,
+{
     type Ok = ();
     type Error = Error;
 
@@ -256,13 +352,72 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     }
 
     fn end(self) -> Result<()> {
-        self.output.push(b'e');
-        self.output.push(b'e');
+        self.writer.write_all(b"ee")?;
         Ok(())
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+pub struct SerializeDict<'a, W> {
+    ser: &'a mut Serializer<W>,
+    // The name of the struct variant whose fields make up the dictionary.
+    variant: Option<&'static str>,
+    // Encoded `(key, value)` pairs, where the key is the raw key bytes.
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    // The key passed to `serialize_key`, waiting for its value.
+    key: Option<Vec<u8>>,
+}
+
+impl<'a, W> SerializeDict<'a, W>
+where
+    W: io::Write,
+{
+    fn insert<T>(&mut self, key: Vec<u8>, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut serializer = Serializer::new(Vec::new());
+        match value.serialize(&mut serializer) {
+            Ok(()) => self.entries.push((key, serializer.into_inner())),
+            // A `None` value leaves the entry out. A `None` nested deeper, in a
+            // list or in a dictionary of its own, has either written output or
+            // recorded a key by the time it fails, and still fails the value.
+            Err(err)
+                if *err.kind() == ErrorKind::NoneNotInDict
+                    && err.path().is_none()
+                    && serializer.writer.is_empty() => {}
+            Err(err) => return Err(err.in_key(&key)),
+        }
+        Ok(())
+    }
+
+    // BEP 3 requires keys to appear in sorted order, compared as raw byte
+    // strings rather than as text.
+    fn write(mut self) -> Result<()> {
+        self.entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if self.entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(ErrorKind::DuplicateKey.into());
+        }
+        if let Some(variant) = self.variant {
+            self.ser.writer.write_all(b"d")?;
+            ser::Serializer::serialize_str(&mut *self.ser, variant)?;
+        }
+        self.ser.writer.write_all(b"d")?;
+        for (key, value) in &self.entries {
+            ser::Serializer::serialize_bytes(&mut *self.ser, key)?;
+            self.ser.writer.write_all(value)?;
+        }
+        self.ser.writer.write_all(b"e")?;
+        if self.variant.is_some() {
+            self.ser.writer.write_all(b"e")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W> ser::SerializeMap for SerializeDict<'a, W>
+where
+    W: io::Write,
+{
     type Ok = ();
     type Error = Error;
 
@@ -270,23 +425,29 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        let key = self.key.take().ok_or_else(|| {
+            <Error as ser::Error>::custom("serialize_value called before serialize_key")
+        })?;
+        self.insert(key, value)
     }
 
     fn end(self) -> Result<()> {
-        self.output.push(b'e');
-        Ok(())
+        self.write()
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl<'a, W> ser::SerializeStruct for SerializeDict<'a, W>
+where
+    W: io::Write,
+{
     type Ok = ();
     type Error = Error;
 
@@ -294,17 +455,18 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        self.insert(key.as_bytes().to_vec(), value)
     }
 
     fn end(self) -> Result<()> {
-        self.output.push(b'e');
-        Ok(())
+        self.write()
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl<'a, W> ser::SerializeStructVariant for SerializeDict<'a, W>
+where
+    W: io::Write,
+{
     type Ok = ();
     type Error = Error;
 
@@ -312,17 +474,364 @@ impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        self.insert(key.as_bytes().to_vec(), value)
     }
 
     fn end(self) -> Result<()> {
-        self.output.push(b'e');
-        self.output.push(b'e');
+        self.write()
+    }
+}
+
+// Extracts the raw bytes of a dictionary key. Keys can be strings, byte
+// strings, sequences of bytes, integers, which are written in decimal, and unit
+// variants, which are written as their name.
+pub(crate) struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    type SerializeSeq = KeyBytes;
+    type SerializeTuple = KeyBytes;
+    type SerializeTupleStruct = KeyBytes;
+    type SerializeTupleVariant = Impossible<Vec<u8>, Error>;
+    type SerializeMap = Impossible<Vec<u8>, Error>;
+    type SerializeStruct = Impossible<Vec<u8>, Error>;
+    type SerializeStructVariant = Impossible<Vec<u8>, Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Vec<u8>> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Vec<u8>> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Vec<u8>> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<u8>> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>> {
+        Ok(v.as_bytes().to_vec())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>> {
+        Ok(v.to_vec())
+    }
+
+    fn serialize_none(self) -> Result<Vec<u8>> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_unit(self) -> Result<Vec<u8>> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<u8>> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<KeyBytes> {
+        Ok(KeyBytes {
+            bytes: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<KeyBytes> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<KeyBytes> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+}
+
+// Collects a key given as a sequence of bytes, such as `Vec<u8>` or a hash
+// `[u8; 20]`, which Serde serializes element by element.
+pub(crate) struct KeyBytes {
+    bytes: Vec<u8>,
+}
+
+impl KeyBytes {
+    fn push<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.bytes.push(value.serialize(ByteSerializer)?);
         Ok(())
     }
 }
 
+// Serializes an element of a key given as a sequence of bytes, which must be
+// a `u8`.
+struct ByteSerializer;
+
+macro_rules! reject_non_byte {
+    ($($serialize:ident($($ty:ty),*),)*) => {
+        $(
+            fn $serialize(self, $(_: $ty),*) -> Result<u8> {
+                Err(ErrorKind::KeyMustBeString.into())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for ByteSerializer {
+    type Ok = u8;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<u8, Error>;
+    type SerializeTuple = Impossible<u8, Error>;
+    type SerializeTupleStruct = Impossible<u8, Error>;
+    type SerializeTupleVariant = Impossible<u8, Error>;
+    type SerializeMap = Impossible<u8, Error>;
+    type SerializeStruct = Impossible<u8, Error>;
+    type SerializeStructVariant = Impossible<u8, Error>;
+
+    fn serialize_u8(self, v: u8) -> Result<u8> {
+        Ok(v)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<u8>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    reject_non_byte! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<u8>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<u8>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(ErrorKind::KeyMustBeString.into())
+    }
+}
+
+impl ser::SerializeSeq for KeyBytes {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        Ok(self.bytes)
+    }
+}
+
+impl ser::SerializeTuple for KeyBytes {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        Ok(self.bytes)
+    }
+}
+
+impl ser::SerializeTupleStruct for KeyBytes {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        Ok(self.bytes)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[test]
@@ -370,9 +879,296 @@ fn test_struct() {
         hello: serde_bytes::Bytes::new(b"World"),
         spam: vec!["a", "b"],
     };
-    let expected = b"d3:cow3:mow5:valuei1e5:hello5:World4:spaml1:a1:bee";
+    let expected = b"d3:cow3:mow5:hello5:World4:spaml1:a1:be5:valuei1ee";
     let res = &to_bytes(&test).unwrap()[..];
     let a = String::from_utf8_lossy(res);
     println!("{}", a);
     assert_eq!(res, expected);
 }
+
+#[test]
+fn test_negative_integer() {
+    assert_eq!(to_bytes(&-42).unwrap(), b"i-42e");
+    assert_eq!(to_bytes(&0).unwrap(), b"i0e");
+    assert_eq!(to_bytes(&i64::MIN).unwrap(), b"i-9223372036854775808e");
+    assert_eq!(to_bytes(&u64::MAX).unwrap(), b"i18446744073709551615e");
+}
+
+#[test]
+fn test_long_string() {
+    let long = "a".repeat(300);
+    let mut expected = b"300:".to_vec();
+    expected.extend_from_slice(long.as_bytes());
+    assert_eq!(to_bytes(&long).unwrap(), expected);
+    assert_eq!(to_bytes(&"0123456789").unwrap(), b"10:0123456789");
+}
+
+#[test]
+fn test_struct_keys_sorted() {
+    #[derive(Serialize)]
+    struct Info {
+        name: &'static str,
+        #[serde(rename = "piece length")]
+        piece_length: u32,
+        length: u32,
+        #[serde(with = "serde_bytes")]
+        pieces: Vec<u8>,
+    }
+
+    let info = Info {
+        name: "a.txt",
+        piece_length: 16384,
+        length: 3,
+        pieces: vec![0xff; 20],
+    };
+    let mut expected = b"d6:lengthi3e4:name5:a.txt12:piece lengthi16384e6:pieces20:".to_vec();
+    expected.extend_from_slice(&[0xff; 20]);
+    expected.push(b'e');
+    assert_eq!(to_bytes(&info).unwrap(), expected);
+}
+
+// Serde only implements `Serialize` for `HashMap` with `std`.
+#[cfg(feature = "std")]
+#[test]
+fn test_map_keys_sorted_as_bytes() {
+    use std::collections::HashMap;
+    let mut map = HashMap::new();
+    map.insert("b", 1);
+    map.insert("a", 2);
+    map.insert("B", 3);
+    map.insert("ab", 4);
+    map.insert("\u{e9}", 5);
+
+    let expected = "d1:Bi3e1:ai2e2:abi4e1:bi1e2:\u{e9}i5ee".as_bytes();
+    assert_eq!(to_bytes(&map).unwrap(), expected);
+}
+
+#[test]
+fn test_duplicate_keys() {
+    #[derive(Serialize)]
+    struct Inner {
+        name: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct Outer {
+        name: &'static str,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+
+    let outer = Outer {
+        name: "a",
+        inner: Inner { name: "b" },
+    };
+    assert_eq!(
+        to_bytes(&outer).unwrap_err().kind(),
+        &ErrorKind::DuplicateKey
+    );
+}
+
+#[test]
+fn test_non_string_key() {
+    use std::collections::BTreeMap;
+    let mut map = BTreeMap::new();
+    map.insert(vec!["a"], 1);
+    assert_eq!(
+        to_bytes(&map).unwrap_err().kind(),
+        &ErrorKind::KeyMustBeString
+    );
+}
+
+#[test]
+fn test_byte_and_integer_keys() {
+    use serde_bytes::ByteBuf;
+    use std::collections::BTreeMap;
+
+    // BEP 10 extension handshakes map names to message IDs.
+    let mut m = BTreeMap::new();
+    m.insert(b"ut_pex".to_vec(), 2);
+    m.insert(b"ut_metadata".to_vec(), 1);
+    assert_eq!(to_bytes(&m).unwrap(), b"d11:ut_metadatai1e6:ut_pexi2ee");
+
+    // BEP 52 piece layers are keyed by binary hashes.
+    #[cfg(feature = "std")]
+    {
+        let mut layers = std::collections::HashMap::new();
+        layers.insert(ByteBuf::from(vec![0xff; 2]), "b");
+        layers.insert(ByteBuf::from(vec![0x00; 2]), "a");
+        assert_eq!(to_bytes(&layers).unwrap(), b"d2:\x00\x001:a2:\xff\xff1:be");
+    }
+    let mut layers = BTreeMap::new();
+    layers.insert(ByteBuf::from(vec![0xff; 2]), "b");
+    assert_eq!(to_bytes(&layers).unwrap(), b"d2:\xff\xff1:be");
+    let mut hashes = BTreeMap::new();
+    hashes.insert([0xab_u8; 3], 1);
+    assert_eq!(to_bytes(&hashes).unwrap(), b"d3:\xab\xab\xabi1ee");
+
+    // Integer keys are written in decimal and sorted as strings.
+    let mut pieces = BTreeMap::new();
+    pieces.insert(2_u32, "b");
+    pieces.insert(10_u32, "a");
+    pieces.insert(-1_i32 as u32, "c");
+    assert_eq!(
+        to_bytes(&pieces).unwrap(),
+        b"d2:101:a1:21:b10:42949672951:ce"
+    );
+
+    // Only sequences of `u8` are byte strings, whatever their values.
+    let mut map = BTreeMap::new();
+    map.insert(vec![1_u16], 1);
+    assert_eq!(
+        to_bytes(&map).unwrap_err().kind(),
+        &ErrorKind::KeyMustBeString
+    );
+    let mut map = BTreeMap::new();
+    map.insert(vec![vec![1_u8]], 1);
+    assert_eq!(
+        to_bytes(&map).unwrap_err().kind(),
+        &ErrorKind::KeyMustBeString
+    );
+    let mut map = BTreeMap::new();
+    map.insert(Some("a"), 1);
+    assert_eq!(
+        to_bytes(&map).unwrap_err().kind(),
+        &ErrorKind::KeyMustBeString
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_to_writer() {
+    #[derive(Serialize)]
+    struct Request<'a> {
+        peer_id: &'a str,
+        port: u16,
+    }
+
+    let mut output = Vec::new();
+    let request = Request {
+        peer_id: "-DE203s-x49Ta1Q*sgGQ",
+        port: 6881,
+    };
+    to_writer(&mut output, &request).unwrap();
+    assert_eq!(output, b"d7:peer_id20:-DE203s-x49Ta1Q*sgGQ4:porti6881ee");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_to_writer_io_error() {
+    use std::io;
+
+    struct Full;
+
+    impl io::Write for Full {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let err = to_writer(Full, &"spam").unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::Io(io::ErrorKind::WriteZero));
+    assert_eq!(err.to_string(), "I/O error: disk full");
+}
+
+#[test]
+fn test_data_model() {
+    #[derive(Serialize)]
+    struct Unit;
+
+    #[derive(Serialize)]
+    enum Event {
+        Started,
+        Completed(u64),
+        Range(u32, u32),
+        Stopped { uploaded: u64, downloaded: u64 },
+    }
+
+    assert_eq!(to_bytes(&true).unwrap(), b"i1e");
+    assert_eq!(to_bytes(&false).unwrap(), b"i0e");
+    assert_eq!(to_bytes(&'\u{e9}').unwrap(), "2:\u{e9}".as_bytes());
+    assert_eq!(to_bytes(&()).unwrap(), b"0:");
+    assert_eq!(to_bytes(&Unit).unwrap(), b"0:");
+    assert_eq!(to_bytes(&Some(3)).unwrap(), b"i3e");
+    assert_eq!(
+        to_bytes(&serde_bytes::ByteBuf::from(vec![0xff])).unwrap(),
+        b"1:\xff"
+    );
+    assert_eq!(to_bytes(&Event::Started).unwrap(), b"7:Started");
+    assert_eq!(to_bytes(&Event::Completed(5)).unwrap(), b"d9:Completedi5ee");
+    assert_eq!(to_bytes(&Event::Range(1, 2)).unwrap(), b"d5:Rangeli1ei2eee");
+    assert_eq!(
+        to_bytes(&Event::Stopped {
+            uploaded: 1,
+            downloaded: 2
+        })
+        .unwrap(),
+        b"d7:Stoppedd10:downloadedi2e8:uploadedi1eee"
+    );
+    assert_eq!(
+        to_bytes(&1.5).unwrap_err().kind(),
+        &ErrorKind::UnsupportedType
+    );
+}
+
+#[test]
+fn test_none_fields() {
+    #[derive(Serialize)]
+    struct Peer<'a> {
+        #[serde(rename = "peer id")]
+        peer_id: Option<&'a str>,
+        ip: &'a str,
+        port: Option<u16>,
+    }
+
+    let peer = Peer {
+        peer_id: None,
+        ip: "10.0.0.1",
+        port: Some(6881),
+    };
+    assert_eq!(to_bytes(&peer).unwrap(), b"d2:ip8:10.0.0.14:porti6881ee");
+
+    assert_eq!(
+        to_bytes(&None::<u8>).unwrap_err().kind(),
+        &ErrorKind::NoneNotInDict
+    );
+    assert_eq!(
+        to_bytes(&vec![Some(1), None]).unwrap_err().kind(),
+        &ErrorKind::NoneNotInDict
+    );
+
+    // A `None` inside a list inside a dictionary is not an omitted entry.
+    #[derive(Serialize)]
+    struct Inner {
+        list: Vec<Option<u8>>,
+    }
+
+    #[derive(Serialize)]
+    struct Outer {
+        inner: Inner,
+    }
+
+    let outer = Outer {
+        inner: Inner { list: vec![None] },
+    };
+    let err = to_bytes(&outer).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::NoneNotInDict);
+    assert_eq!(err.path().as_deref(), Some("inner.list"));
+}
+
+#[test]
+fn test_value_before_key() {
+    use serde::ser::{SerializeMap, Serializer as _};
+
+    let mut serializer = Serializer::new(Vec::new());
+    let mut map = (&mut serializer).serialize_map(None).unwrap();
+    let err = map.serialize_value(&1).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        &ErrorKind::Message("serialize_value called before serialize_key".into())
+    );
+}
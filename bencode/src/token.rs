@@ -0,0 +1,560 @@
+//! A low-level pull parser for bencode.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::de::{Mode, Options};
+use crate::error::{Error, ErrorKind, Result};
+
+/// A single piece of bencode syntax.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// The `d` opening a dictionary. It is followed by alternating
+    /// [`Token::Key`]s and values, and then by [`Token::End`].
+    DictStart,
+    /// The `l` opening a list. It is followed by its elements, and then by
+    /// [`Token::End`].
+    ListStart,
+    /// An integer.
+    Int(i64),
+    /// An integer outside the range of `i64`, as its decimal digits with an
+    /// optional leading `-`, borrowed from the input. BEP 3 puts no bound on
+    /// integers, so such values are valid bencode.
+    BigInt(&'a [u8]),
+    /// A byte string value, borrowed from the input.
+    Bytes(&'a [u8]),
+    /// A dictionary key, borrowed from the input. It is followed by the
+    /// value of the entry.
+    Key(&'a [u8]),
+    /// The `e` closing the innermost dictionary or list.
+    End,
+}
+
+/// An iterator over the tokens of a single bencode value, each paired with
+/// the byte offset at which it starts.
+///
+/// The tokenizer checks the structure of the input as it goes, so that keys
+/// are strings, containers are closed, and the [`Options`] it was created
+/// with are respected. After an error it yields nothing more.
+///
+/// Combined with [`Tokenizer::skip_value`] it can pick values out of a large
+/// document without decoding the rest, for example to find the bytes of
+/// the info dictionary:
+///
+/// ```
+/// # use bencode::{Token, Tokenizer};
+/// let input = b"d8:announce3:url4:infod4:name1:aee";
+/// let mut tokens = Tokenizer::new(input);
+/// let mut info = None;
+/// while let Some((_, token)) = tokens.next_token().unwrap() {
+///     if tokens.depth() == 1 && token == Token::Key(b"info") {
+///         info = Some(tokens.skip_value().unwrap());
+///     }
+/// }
+/// assert_eq!(&input[info.unwrap()], b"d4:name1:ae");
+/// ```
+pub struct Tokenizer<'a> {
+    // The complete input data. Bencode strings are arbitrary byte sequences,
+    // so the tokenizer works on bytes and leaves UTF-8 validation to its
+    // users.
+    pub(crate) input: &'a [u8],
+    // Byte offset of the next unread byte in `input`.
+    pub(crate) index: usize,
+    pub(crate) options: Options,
+    // The number of lists and dictionaries currently open.
+    depth: usize,
+    // The number of list elements and dictionary entries read so far.
+    items: usize,
+    // The containers opened by `next_token`, innermost last.
+    stack: Vec<Frame<'a>>,
+    // Set once `next_token` has returned an error or reached the end.
+    done: bool,
+}
+
+enum Frame<'a> {
+    List,
+    Dict {
+        // The previous key, to check the key order in strict mode.
+        last_key: Option<&'a [u8]>,
+        // Whether a key has been read and its value is next.
+        in_value: bool,
+    },
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Tokenizer::with_options(input, Options::default())
+    }
+
+    pub fn with_options(input: &'a [u8], options: Options) -> Self {
+        Tokenizer {
+            input,
+            index: 0,
+            options,
+            depth: 0,
+            items: 0,
+            stack: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// The byte offset of the next unread byte.
+    pub fn offset(&self) -> usize {
+        self.index
+    }
+
+    /// The number of dictionaries and lists currently open.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Reads the next token and the offset at which it starts, or returns
+    /// `None` once a complete value has been read.
+    ///
+    /// Trailing data after the value is an error unless the mode is
+    /// [`Mode::Lenient`].
+    pub fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>> {
+        if self.done {
+            return Ok(None);
+        }
+        let token = self.read_token();
+        if !matches!(token, Ok(Some(_))) {
+            self.done = true;
+        }
+        token
+    }
+
+    /// Skips over the next complete value, including everything nested in
+    /// it, and returns its byte span.
+    pub fn skip_value(&mut self) -> Result<Range<usize>> {
+        let depth = self.depth;
+        let start = match self.next_token()? {
+            Some((_, Token::End)) | None => {
+                return Err(Error::new(ErrorKind::Syntax, Some(self.index)))
+            }
+            Some((start, _)) => start,
+        };
+        while self.depth > depth {
+            if self.next_token()?.is_none() {
+                return Err(Error::new(ErrorKind::Eof, Some(self.index)));
+            }
+        }
+        Ok(start..self.index)
+    }
+
+    /// Checks that the whole input has been consumed.
+    pub(crate) fn end(&self) -> Result<()> {
+        if self.index == self.input.len() {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::TrailingCharacters, Some(self.index)))
+        }
+    }
+
+    fn read_token(&mut self) -> Result<Option<(usize, Token<'a>)>> {
+        // A complete top-level value has been read.
+        if self.stack.is_empty() && self.index > 0 {
+            if self.options.mode != Mode::Lenient {
+                self.end()?;
+            }
+            return Ok(None);
+        }
+        let start = self.index;
+        let byte = self.peek_byte()?;
+        match self.stack.last() {
+            Some(Frame::Dict {
+                in_value: false, ..
+            }) if byte != b'e' => return self.read_key().map(Some),
+            Some(Frame::List) if byte != b'e' => self.count_item(start)?,
+            _ => {}
+        }
+        let token = match byte {
+            b'i' => {
+                let digits = self.parse_integer_digits()?;
+                match integer_from_digits(digits) {
+                    Some(int) => Token::Int(int),
+                    None => Token::BigInt(digits),
+                }
+            }
+            b'0'..=b'9' => Token::Bytes(self.parse_bytes()?),
+            b'l' => {
+                self.enter(start)?;
+                self.index += 1;
+                self.stack.push(Frame::List);
+                return Ok(Some((start, Token::ListStart)));
+            }
+            b'd' => {
+                self.enter(start)?;
+                self.index += 1;
+                self.stack.push(Frame::Dict {
+                    last_key: None,
+                    in_value: false,
+                });
+                return Ok(Some((start, Token::DictStart)));
+            }
+            // Closes a list, or a dictionary that is not waiting for a value.
+            b'e' if matches!(
+                self.stack.last(),
+                Some(
+                    Frame::List
+                        | Frame::Dict {
+                            in_value: false,
+                            ..
+                        }
+                )
+            ) =>
+            {
+                self.index += 1;
+                self.stack.pop();
+                self.leave();
+                Token::End
+            }
+            _ => return Err(Error::new(ErrorKind::Syntax, Some(start))),
+        };
+        // The token completed a value, so the enclosing dictionary, if any,
+        // expects a key next.
+        if let Some(Frame::Dict { in_value, .. }) = self.stack.last_mut() {
+            *in_value = false;
+        }
+        Ok(Some((start, token)))
+    }
+
+    fn read_key(&mut self) -> Result<(usize, Token<'a>)> {
+        let start = self.index;
+        let previous = match self.stack.last() {
+            Some(Frame::Dict { last_key, .. }) => *last_key,
+            _ => None,
+        };
+        let key = self.parse_key(previous)?;
+        if let Some(Frame::Dict { last_key, in_value }) = self.stack.last_mut() {
+            *last_key = Some(key);
+            *in_value = true;
+        }
+        Ok((start, Token::Key(key)))
+    }
+}
+
+// Checks the form of the digits of an integer, with the sign: decimal digits
+// and, unless `mode` is lenient, no leading zero or `-0`. Integer dictionary
+// keys are held to the same rules as integer values.
+pub(crate) fn check_integer_digits(
+    digits: &[u8],
+    mode: Mode,
+) -> core::result::Result<(), ErrorKind> {
+    let (negative, magnitude) = match digits {
+        [b'-', magnitude @ ..] => (true, magnitude),
+        magnitude => (false, magnitude),
+    };
+    if !magnitude.iter().all(u8::is_ascii_digit) {
+        return Err(ErrorKind::ExpectedInteger);
+    }
+    let lenient = mode == Mode::Lenient;
+    match magnitude {
+        [] => Err(ErrorKind::ExpectedInteger),
+        [b'0'] if negative && !lenient => Err(ErrorKind::NegativeZero),
+        [b'0', _, ..] if !lenient => Err(ErrorKind::LeadingZero),
+        _ => Ok(()),
+    }
+}
+
+// Converts the digits of an integer, as returned by `parse_integer_digits`,
+// or returns `None` if it does not fit in `T`. The value is accumulated in an
+// `i128`, which holds every `i64` and `u64`, and only then narrowed so that
+// overflow is reported for each target type instead of wrapping.
+pub(crate) fn integer_from_digits<T>(digits: &[u8]) -> Option<T>
+where
+    T: TryFrom<i128>,
+{
+    let (negative, magnitude) = match digits {
+        [b'-', magnitude @ ..] => (true, magnitude),
+        magnitude => (false, magnitude),
+    };
+    let int = magnitude.iter().try_fold(0_i128, |int, ch| {
+        int.checked_mul(10)?.checked_add(i128::from(ch - b'0'))
+    })?;
+    T::try_from(if negative { -int } else { int }).ok()
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<(usize, Token<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
+    }
+}
+
+// The lower-level parsing methods shared with the `Deserializer`.
+impl<'a> Tokenizer<'a> {
+    pub(crate) fn peek_byte(&mut self) -> Result<u8> {
+        self.input
+            .get(self.index)
+            .copied()
+            .ok_or_else(|| Error::new(ErrorKind::Eof, Some(self.index)))
+    }
+
+    pub(crate) fn next_byte(&mut self) -> Result<u8> {
+        let byte = self.peek_byte()?;
+        self.index += 1;
+        Ok(byte)
+    }
+
+    // Enters a list or dictionary starting at `start`.
+    pub(crate) fn enter(&mut self, start: usize) -> Result<()> {
+        if self.depth >= self.options.max_depth {
+            return Err(Error::new(ErrorKind::DepthLimitExceeded, Some(start)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    // Counts a list element or dictionary entry starting at `start`.
+    pub(crate) fn count_item(&mut self, start: usize) -> Result<()> {
+        if self.items >= self.options.max_items {
+            return Err(Error::new(ErrorKind::TooManyItems, Some(start)));
+        }
+        self.items += 1;
+        Ok(())
+    }
+
+    // Parses an `i<digits>e` integer and converts it to the requested width.
+    pub(crate) fn parse_integer<T>(&mut self) -> Result<T>
+    where
+        T: TryFrom<i128>,
+    {
+        let token = self.index;
+        let digits = self.parse_integer_digits()?;
+        integer_from_digits(digits)
+            .ok_or_else(|| Error::new(ErrorKind::IntegerOverflow, Some(token)))
+    }
+
+    // Parses an `i<digits>e` integer and returns its digits, with the sign,
+    // borrowed from the input. Only the form of the integer is checked here,
+    // not its range.
+    pub(crate) fn parse_integer_digits(&mut self) -> Result<&'a [u8]> {
+        let token = self.index;
+        if self.next_byte()? != b'i' {
+            return Err(Error::new(ErrorKind::ExpectedInteger, Some(token)));
+        }
+        let start = self.index;
+        let negative = self.peek_byte()? == b'-';
+        if negative {
+            self.index += 1;
+        }
+        loop {
+            match self.next_byte()? {
+                b'0'..=b'9' => {}
+                b'e' => break,
+                _ => return Err(Error::new(ErrorKind::ExpectedInteger, Some(self.index - 1))),
+            }
+        }
+        let digits = &self.input[start..self.index - 1];
+        check_integer_digits(digits, self.options.mode)
+            .map_err(|kind| Error::new(kind, Some(token)))?;
+        Ok(digits)
+    }
+
+    // Parses a `<length>:<contents>` byte string and returns its contents,
+    // borrowed from the input.
+    pub(crate) fn parse_bytes(&mut self) -> Result<&'a [u8]> {
+        let start = self.index;
+        let mut len = match self.next_byte()? {
+            ch @ b'0'..=b'9' => usize::from(ch - b'0'),
+            _ => {
+                return Err(Error::new(ErrorKind::ExpectedString, Some(start)));
+            }
+        };
+        loop {
+            match self.next_byte()? {
+                ch @ b'0'..=b'9' => {
+                    len = len
+                        .checked_mul(10)
+                        .and_then(|len| len.checked_add(usize::from(ch - b'0')))
+                        .ok_or_else(|| Error::new(ErrorKind::IntegerOverflow, Some(start)))?;
+                }
+                b':' => break,
+                _ => return Err(Error::new(ErrorKind::ExpectedString, Some(self.index - 1))),
+            }
+        }
+        if len > self.options.max_string_length {
+            return Err(Error::new(ErrorKind::StringTooLong, Some(start)));
+        }
+        if self.options.mode == Mode::Strict && self.input[start] == b'0' && len > 0 {
+            return Err(Error::new(ErrorKind::LeadingZero, Some(start)));
+        }
+        let bytes = self
+            .index
+            .checked_add(len)
+            .and_then(|end| self.input.get(self.index..end))
+            .ok_or_else(|| Error::new(ErrorKind::Eof, Some(self.input.len())))?;
+        self.index += len;
+        Ok(bytes)
+    }
+
+    // Parses a dictionary key, counting the entry it starts. In strict mode
+    // the key must be greater than `last_key`, the previous key of the same
+    // dictionary.
+    pub(crate) fn parse_key(&mut self, last_key: Option<&'a [u8]>) -> Result<&'a [u8]> {
+        let start = self.index;
+        self.count_item(start)?;
+        let key = match self.parse_bytes() {
+            Ok(key) => key,
+            Err(err) if *err.kind() == ErrorKind::ExpectedString => {
+                return Err(Error::new(ErrorKind::KeyMustBeString, Some(start)))
+            }
+            Err(err) => return Err(err),
+        };
+        if self.options.mode == Mode::Strict {
+            match last_key {
+                Some(last) if last == key => {
+                    return Err(Error::new(ErrorKind::DuplicateKey, Some(start)))
+                }
+                Some(last) if last > key => {
+                    return Err(Error::new(ErrorKind::UnsortedKey, Some(start)))
+                }
+                _ => {}
+            }
+        }
+        Ok(key)
+    }
+
+    pub(crate) fn parse_string(&mut self) -> Result<&'a str> {
+        let start = self.index;
+        core::str::from_utf8(self.parse_bytes()?)
+            .map_err(|_| Error::new(ErrorKind::InvalidUtf8, Some(start)))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_tokens() {
+    let tokens: Vec<_> = Tokenizer::new(b"d3:bari-7e3:fooli1e0:ee")
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(
+        tokens,
+        vec![
+            (0, Token::DictStart),
+            (1, Token::Key(b"bar")),
+            (6, Token::Int(-7)),
+            (10, Token::Key(b"foo")),
+            (15, Token::ListStart),
+            (16, Token::Int(1)),
+            (19, Token::Bytes(b"")),
+            (21, Token::End),
+            (22, Token::End),
+        ]
+    );
+}
+
+#[test]
+fn test_big_integers() {
+    let tokens: Vec<_> =
+        Tokenizer::new(b"li9223372036854775807ei18446744073709551615ei-9223372036854775809ee")
+            .map(|token| token.unwrap().1)
+            .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::ListStart,
+            Token::Int(i64::MAX),
+            Token::BigInt(b"18446744073709551615"),
+            Token::BigInt(b"-9223372036854775809"),
+            Token::End,
+        ]
+    );
+
+    // Their form is checked like that of any other integer.
+    let err = Tokenizer::new(b"i018446744073709551615e")
+        .next_token()
+        .unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::LeadingZero);
+}
+
+#[test]
+fn test_token_errors() {
+    fn first_error(input: &[u8], options: Options) -> ErrorKind {
+        Tokenizer::with_options(input, options)
+            .find_map(|token| token.err())
+            .unwrap()
+            .kind()
+            .clone()
+    }
+
+    let options = Options::default;
+    assert_eq!(
+        first_error(b"di1ei2ee", options()),
+        ErrorKind::KeyMustBeString
+    );
+    assert_eq!(first_error(b"d1:ae", options()), ErrorKind::Syntax);
+    assert_eq!(first_error(b"li1e", options()), ErrorKind::Eof);
+    assert_eq!(first_error(b"e", options()), ErrorKind::Syntax);
+    assert_eq!(
+        first_error(b"i1ei2e", options()),
+        ErrorKind::TrailingCharacters
+    );
+    assert_eq!(first_error(b"i-0e", options()), ErrorKind::NegativeZero);
+    assert_eq!(
+        first_error(b"d1:bi1e1:ai2ee", Options::strict()),
+        ErrorKind::UnsortedKey
+    );
+    assert_eq!(
+        first_error(b"lli1eee", options().max_depth(1)),
+        ErrorKind::DepthLimitExceeded
+    );
+    assert_eq!(
+        Tokenizer::with_options(b"i1ei2e", Options::lenient()).count(),
+        1
+    );
+
+    // Nothing is yielded after an error.
+    let mut tokens = Tokenizer::new(b"lxi1ee");
+    assert!(tokens.next().unwrap().is_ok());
+    assert!(tokens.next().unwrap().is_err());
+    assert!(tokens.next().is_none());
+}
+
+#[test]
+fn test_skip_value() {
+    #[derive(serde::Deserialize)]
+    struct Metainfo<'a> {
+        #[serde(borrow)]
+        info: crate::RawValue<'a>,
+    }
+
+    let data = include_bytes!("../fixtures/multi-file.torrent");
+    let mut tokens = Tokenizer::new(data);
+    let mut info = None;
+    while let Some((_, token)) = tokens.next_token().unwrap() {
+        match token {
+            Token::Key(b"info") if tokens.depth() == 1 => {
+                info = Some(tokens.skip_value().unwrap());
+            }
+            Token::Key(b"announce") if tokens.depth() == 1 => {
+                assert_eq!(tokens.skip_value().unwrap().len(), 43);
+            }
+            _ => {}
+        }
+    }
+    let metainfo: Metainfo = crate::from_bytes(data).unwrap();
+    assert_eq!(&data[info.unwrap()], metainfo.info.as_bytes());
+
+    // A value equal to a key is not mistaken for one.
+    let mut tokens = Tokenizer::new(b"d1:a4:infoe");
+    let keys: Vec<_> = tokens
+        .by_ref()
+        .filter_map(|token| match token.unwrap() {
+            (_, Token::Key(key)) => Some(key),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(keys, [b"a"]);
+
+    let mut tokens = Tokenizer::new(b"le");
+    tokens.next_token().unwrap();
+    assert_eq!(tokens.skip_value().unwrap_err().kind(), &ErrorKind::Syntax);
+}
@@ -0,0 +1,343 @@
+//! A borrowed representation of any bencode value.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Index;
+use core::str;
+
+use serde::de::{self, MapAccess, SeqAccess, Unexpected, Visitor};
+use serde::ser::{self, SerializeMap};
+use serde::{Deserialize, Serialize};
+use serde_bytes::Bytes;
+
+use super::Value;
+
+/// Any valid bencode value, borrowing its byte strings and dictionary keys
+/// from the input.
+///
+/// Decoding a `ValueRef` only allocates for lists and dictionaries, which
+/// makes it cheaper than [`Value`] for messages that are inspected once and
+/// dropped. It can be deserialized from a borrowed input such as the one
+/// given to [`from_bytes`](crate::from_bytes), including as a field marked
+/// `#[serde(borrow)]`, and converted into a `Value` with
+/// [`ValueRef::into_owned`].
+///
+/// ```
+/// # use bencode::ValueRef;
+/// let message = b"d1:md11:ut_metadatai3ee1:v6:client1:pi6881ee";
+/// let value: ValueRef = bencode::from_bytes(message).unwrap();
+/// assert_eq!(value["m"]["ut_metadata"].as_int(), Some(3));
+/// assert_eq!(value.get("v").and_then(ValueRef::as_str), Some("client"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValueRef<'a> {
+    /// An integer, `i<digits>e`.
+    Int(i64),
+    /// A byte string, `<length>:<contents>`.
+    Bytes(&'a [u8]),
+    /// A list, `l<values>e`.
+    List(Vec<ValueRef<'a>>),
+    /// A dictionary, `d<key><value>...e`.
+    Dict(BTreeMap<&'a [u8], ValueRef<'a>>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Returns the integer if this value is an `Int`.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            ValueRef::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw bytes if this value is a byte string.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            ValueRef::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if this value is a byte string holding valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes().and_then(|bytes| str::from_utf8(bytes).ok())
+    }
+
+    /// Returns the elements if this value is a `List`.
+    pub fn as_list(&self) -> Option<&Vec<ValueRef<'a>>> {
+        match self {
+            ValueRef::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries if this value is a `Dict`.
+    pub fn as_dict(&self) -> Option<&BTreeMap<&'a [u8], ValueRef<'a>>> {
+        match self {
+            ValueRef::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Mutable counterpart of [`ValueRef::as_list`].
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<ValueRef<'a>>> {
+        match self {
+            ValueRef::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Mutable counterpart of [`ValueRef::as_dict`].
+    pub fn as_dict_mut(&mut self) -> Option<&mut BTreeMap<&'a [u8], ValueRef<'a>>> {
+        match self {
+            ValueRef::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if this value is a `Dict`.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&ValueRef<'a>> {
+        self.as_dict().and_then(|dict| dict.get(key.as_ref()))
+    }
+
+    /// Looks up the element at `index` if this value is a `List`.
+    pub fn get_index(&self, index: usize) -> Option<&ValueRef<'a>> {
+        self.as_list().and_then(|list| list.get(index))
+    }
+
+    /// Copies the borrowed byte strings into an owned [`Value`].
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Int(int) => Value::Int(int),
+            ValueRef::Bytes(bytes) => Value::Bytes(bytes.to_vec()),
+            ValueRef::List(list) => {
+                Value::List(list.into_iter().map(ValueRef::into_owned).collect())
+            }
+            ValueRef::Dict(dict) => Value::Dict(
+                dict.into_iter()
+                    .map(|(key, value)| (key.to_vec(), value.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<'a> From<&'a Value> for ValueRef<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Int(int) => ValueRef::Int(*int),
+            Value::Bytes(bytes) => ValueRef::Bytes(bytes),
+            Value::List(list) => ValueRef::List(list.iter().map(ValueRef::from).collect()),
+            Value::Dict(dict) => ValueRef::Dict(
+                dict.iter()
+                    .map(|(key, value)| (&key[..], ValueRef::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef) -> Self {
+        value.into_owned()
+    }
+}
+
+/// Looks up a dictionary entry by key.
+///
+/// # Panics
+///
+/// Panics if the value is not a `Dict` or if the key is missing. Use
+/// [`ValueRef::get`] for a non-panicking lookup.
+impl<'a> Index<&str> for ValueRef<'a> {
+    type Output = ValueRef<'a>;
+
+    fn index(&self, key: &str) -> &ValueRef<'a> {
+        match self {
+            ValueRef::Dict(dict) => dict
+                .get(key.as_bytes())
+                .unwrap_or_else(|| panic!("no entry found for key {:?}", key)),
+            _ => panic!("cannot index into a non-dictionary value with {:?}", key),
+        }
+    }
+}
+
+/// Looks up a list element by position.
+///
+/// # Panics
+///
+/// Panics if the value is not a `List` or if the index is out of bounds. Use
+/// [`ValueRef::get_index`] for a non-panicking lookup.
+impl<'a> Index<usize> for ValueRef<'a> {
+    type Output = ValueRef<'a>;
+
+    fn index(&self, index: usize) -> &ValueRef<'a> {
+        match self {
+            ValueRef::List(list) => &list[index],
+            _ => panic!("cannot index into a non-list value with {}", index),
+        }
+    }
+}
+
+impl Serialize for ValueRef<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            ValueRef::Int(int) => serializer.serialize_i64(*int),
+            ValueRef::Bytes(bytes) => serializer.serialize_bytes(bytes),
+            ValueRef::List(list) => list.serialize(serializer),
+            ValueRef::Dict(dict) => {
+                let mut map = serializer.serialize_map(Some(dict.len()))?;
+                for (key, value) in dict {
+                    map.serialize_entry(Bytes::new(key), value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+// Byte strings can only be borrowed from deserializers that hand out slices
+// of their input, so anything else is rejected.
+impl<'de: 'a, 'a> Deserialize<'de> for ValueRef<'a> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<ValueRef<'a>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueRefVisitor)
+    }
+}
+
+struct ValueRefVisitor;
+
+impl<'de> Visitor<'de> for ValueRefVisitor {
+    type Value = ValueRef<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any bencode value borrowed from the input")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> core::result::Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Int(i64::from(v)))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> core::result::Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> core::result::Result<ValueRef<'de>, E>
+    where
+        E: de::Error,
+    {
+        i64::try_from(v)
+            .map(ValueRef::Int)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> core::result::Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Bytes(v.as_bytes()))
+    }
+
+    fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> core::result::Result<ValueRef<'de>, E> {
+        Ok(ValueRef::Bytes(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> core::result::Result<ValueRef<'de>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+        Ok(ValueRef::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> core::result::Result<ValueRef<'de>, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut dict = BTreeMap::new();
+        while let Some((Key(key), value)) = map.next_entry()? {
+            dict.insert(key, value);
+        }
+        Ok(ValueRef::Dict(dict))
+    }
+}
+
+// A dictionary key borrowed from the input.
+struct Key<'a>(&'a [u8]);
+
+impl<'de: 'a, 'a> Deserialize<'de> for Key<'a> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Key<'a>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = Key<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a byte string borrowed from the input")
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> core::result::Result<Key<'de>, E> {
+                Ok(Key(v.as_bytes()))
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> core::result::Result<Key<'de>, E> {
+                Ok(Key(v))
+            }
+        }
+
+        deserializer.deserialize_bytes(KeyVisitor)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_value_ref() {
+    let input = include_bytes!("../../fixtures/multi-file.torrent");
+    let value: ValueRef = crate::from_bytes(input).unwrap();
+    assert_eq!(value["info"]["files"][2]["length"].as_int(), Some(70001));
+    assert_eq!(
+        value.get("info").and_then(|info| info.get("name")),
+        Some(&ValueRef::Bytes(b"multi"))
+    );
+    assert_eq!(value["info"]["name"].as_str(), Some("multi"));
+    assert_eq!(value.get_index(0), None);
+
+    // The byte strings point into the input.
+    let name = value["info"]["name"].as_bytes().unwrap();
+    assert!(input.as_ptr_range().contains(&name.as_ptr()));
+
+    let owned: Value = crate::from_bytes(input).unwrap();
+    assert_eq!(value.clone().into_owned(), owned);
+    assert_eq!(ValueRef::from(&owned), value);
+    assert_eq!(crate::to_bytes(&value).unwrap(), input);
+}
+
+#[test]
+fn test_value_ref_borrow_field() {
+    #[derive(Deserialize)]
+    struct Message<'a> {
+        msg_type: u8,
+        #[serde(borrow)]
+        extra: Option<ValueRef<'a>>,
+    }
+
+    let message: Message = crate::from_bytes(b"d5:extrald1:ai1eee8:msg_typei1ee").unwrap();
+    assert_eq!(message.msg_type, 1);
+    assert_eq!(
+        message.extra.unwrap().into_owned(),
+        crate::bencode!([{ "a" => 1 }])
+    );
+
+    // Owned input cannot be borrowed from.
+    assert!(ValueRef::deserialize(crate::bencode!("x")).is_err());
+}
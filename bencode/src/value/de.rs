@@ -0,0 +1,417 @@
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize};
+
+use super::Value;
+use crate::de::{KeyDeserializer, Mode};
+use crate::error::{Error, ErrorKind, Result};
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any bencode value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> core::result::Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> core::result::Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> core::result::Result<Value, E>
+    where
+        E: de::Error,
+    {
+        i64::try_from(v)
+            .map(Value::Int)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_str<E>(self, v: &str) -> core::result::Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> core::result::Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> core::result::Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut dict = BTreeMap::new();
+        while let Some((Key(key), value)) = map.next_entry()? {
+            dict.insert(key, value);
+        }
+        Ok(Value::Dict(dict))
+    }
+}
+
+// A dictionary key, accepted as either a byte string or a string.
+struct Key(Vec<u8>);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Key, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = Key;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a byte string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> core::result::Result<Key, E> {
+                Ok(Key(v.as_bytes().to_vec()))
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Key, E> {
+                Ok(Key(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<Key, E> {
+                Ok(Key(v))
+            }
+        }
+
+        deserializer.deserialize_bytes(KeyVisitor)
+    }
+}
+
+/// Interprets a [`Value`] as an instance of type `T`.
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+impl Value {
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Int(int) => Unexpected::Signed(*int),
+            Value::Bytes(bytes) => Unexpected::Bytes(bytes),
+            Value::List(_) => Unexpected::Seq,
+            Value::Dict(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Int(int) => visitor.visit_i64(int),
+            Value::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            Value::List(list) => {
+                let mut seq = SeqDeserializer::new(list.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Dict(dict) => {
+                // A `Value` carries no mode, so integer keys are held to the
+                // default one, as with `from_bytes`.
+                let entries = dict
+                    .into_iter()
+                    .map(|(k, v)| (KeyDeserializer::new(Cow::Owned(k), Mode::default()), v));
+                let mut map = MapDeserializer::new(entries);
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    // Booleans are encoded as the integers 0 and 1.
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Int(0) => visitor.visit_bool(false),
+            Value::Int(1) => visitor.visit_bool(true),
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(bytes) => match String::from_utf8(bytes) {
+                Ok(s) => visitor.visit_string(s),
+                Err(_) => Err(ErrorKind::InvalidUtf8.into()),
+            },
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    // A value that is present is always `Some`; absent dictionary entries are
+    // turned into `None` by the `Deserialize` implementation of the struct.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    // Unit is encoded as the empty byte string.
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(bytes) if bytes.is_empty() => visitor.visit_unit(),
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Enums are externally tagged: unit variants are byte strings holding the
+    // variant name, other variants are single-entry dictionaries mapping the
+    // variant name to its contents.
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(variant) => visitor.visit_enum(EnumDeserializer {
+                variant,
+                value: None,
+            }),
+            Value::Dict(dict) if dict.len() == 1 => {
+                let (variant, value) = dict.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            _ => Err(ErrorKind::ExpectedEnum.into()),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: Vec<u8>,
+    value: Option<Value>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantDeserializer)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(Value::Bytes(self.variant))?;
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer {
+    value: Option<Value>,
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.value {
+            None => Ok(()),
+            Some(_) => Err(ErrorKind::ExpectedString.into()),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => Err(ErrorKind::ExpectedMap.into()),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(value @ Value::List(_)) => de::Deserializer::deserialize_any(value, visitor),
+            _ => Err(ErrorKind::ExpectedArray.into()),
+        }
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(value @ Value::Dict(_)) => de::Deserializer::deserialize_any(value, visitor),
+            _ => Err(ErrorKind::ExpectedMap.into()),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_deserialize_value() {
+    let value: Value = crate::from_bytes(b"d4:spaml1:ai-3ee3:bin2:\xff\x00e").unwrap();
+    let mut expected = BTreeMap::new();
+    expected.insert(
+        b"spam".to_vec(),
+        Value::List(vec![Value::from("a"), Value::Int(-3)]),
+    );
+    expected.insert(b"bin".to_vec(), Value::Bytes(b"\xff\x00".to_vec()));
+    assert_eq!(value, Value::Dict(expected));
+}
+
+#[test]
+fn test_from_value() {
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum Event {
+        Started,
+        Stopped { downloaded: u64 },
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Announce {
+        #[serde(with = "serde_bytes")]
+        info_hash: Vec<u8>,
+        port: u16,
+        compact: bool,
+        ip: Option<String>,
+        events: Vec<Event>,
+    }
+
+    let value: Value =
+        crate::from_bytes(b"d7:compacti1e6:eventsl7:Startedd7:Stoppedd10:downloadedi5eeee9:info_hash2:\xff\x004:porti6881ee").unwrap();
+    let expected = Announce {
+        info_hash: b"\xff\x00".to_vec(),
+        port: 6881,
+        compact: true,
+        ip: None,
+        events: vec![Event::Started, Event::Stopped { downloaded: 5 }],
+    };
+    assert_eq!(from_value::<Announce>(value).unwrap(), expected);
+}
+
+#[test]
+fn test_from_value_errors() {
+    assert!(from_value::<u8>(Value::Int(256)).is_err());
+    assert_eq!(
+        from_value::<String>(Value::Bytes(b"\xff".to_vec()))
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::InvalidUtf8
+    );
+}
+
+#[test]
+fn test_from_value_keys() {
+    use std::collections::BTreeMap;
+
+    let value = crate::bencode! { "1" => "a", "10" => "b" };
+    let map: BTreeMap<u16, String> = from_value(value.clone()).unwrap();
+    assert_eq!(map[&10], "b");
+    let map: BTreeMap<Vec<u8>, String> = from_value(value).unwrap();
+    assert_eq!(map[&b"1"[..]], "a");
+}
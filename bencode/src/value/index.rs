@@ -0,0 +1,54 @@
+use core::ops::Index;
+
+use super::Value;
+
+/// Looks up a dictionary entry by key.
+///
+/// # Panics
+///
+/// Panics if the value is not a `Dict` or if the key is missing. Use
+/// [`Value::get`] for a non-panicking lookup.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        match self {
+            Value::Dict(dict) => dict
+                .get(key.as_bytes())
+                .unwrap_or_else(|| panic!("no entry found for key {:?}", key)),
+            _ => panic!("cannot index into a non-dictionary value with {:?}", key),
+        }
+    }
+}
+
+/// Looks up a list element by position.
+///
+/// # Panics
+///
+/// Panics if the value is not a `List` or if the index is out of bounds. Use
+/// [`Value::get_index`] for a non-panicking lookup.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        match self {
+            Value::List(list) => &list[index],
+            _ => panic!("cannot index into a non-list value with {}", index),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_index() {
+    let value: Value = crate::from_bytes(b"d5:filesld6:lengthi3eeee").unwrap();
+    assert_eq!(value["files"][0]["length"], Value::Int(3));
+}
+
+#[test]
+#[should_panic(expected = "no entry found for key \"missing\"")]
+fn test_index_missing_key() {
+    let value: Value = crate::from_bytes(b"de").unwrap();
+    let _ = &value["missing"];
+}
@@ -0,0 +1,236 @@
+//! Lossless conversion between bencode and JSON values.
+//!
+//! Byte strings that are valid UTF-8 become JSON strings, and other byte
+//! strings become `{"$hex": "<hex digits>"}`. Dictionaries become JSON
+//! objects, unless a key is not valid UTF-8 or the object would be mistaken
+//! for one of these escapes, in which case they become
+//! `{"$dict": [[key, value], ...]}` with each key encoded like a byte
+//! string. Converting back yields the original value, so canonical bencode
+//! survives a round trip through JSON byte for byte.
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt::Write;
+use core::str;
+
+use serde_json::{Map, Number};
+
+use super::Value;
+use crate::error::{Error, ErrorKind, Result};
+
+const HEX: &str = "$hex";
+const DICT: &str = "$dict";
+
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Int(int) => serde_json::Value::Number(Number::from(int)),
+            Value::Bytes(bytes) => bytes_to_json(bytes),
+            Value::List(list) => list.into_iter().map(serde_json::Value::from).collect(),
+            Value::Dict(dict) => {
+                let escape = dict.keys().any(|key| str::from_utf8(key).is_err())
+                    || (dict.len() == 1
+                        && dict
+                            .keys()
+                            .any(|key| key == HEX.as_bytes() || key == DICT.as_bytes()));
+                if escape {
+                    let entries = dict
+                        .into_iter()
+                        .map(|(key, value)| {
+                            serde_json::Value::Array(vec![bytes_to_json(key), value.into()])
+                        })
+                        .collect();
+                    let mut object = Map::new();
+                    object.insert(DICT.to_owned(), serde_json::Value::Array(entries));
+                    serde_json::Value::Object(object)
+                } else {
+                    dict.into_iter()
+                        .map(|(key, value)| {
+                            let key = String::from_utf8(key).expect("key checked above");
+                            (key, serde_json::Value::from(value))
+                        })
+                        .collect()
+                }
+            }
+        }
+    }
+}
+
+/// Converts JSON produced from a [`Value`] back, and accepts any other JSON
+/// that has a bencode equivalent: booleans become `0` or `1`, while `null`,
+/// floats and integers outside the `i64` range are rejected.
+impl TryFrom<serde_json::Value> for Value {
+    type Error = Error;
+
+    fn try_from(json: serde_json::Value) -> Result<Value> {
+        match json {
+            serde_json::Value::Null => Err(ErrorKind::UnsupportedType.into()),
+            serde_json::Value::Bool(b) => Ok(Value::from(b)),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(int) => Ok(Value::Int(int)),
+                None if number.is_f64() => Err(ErrorKind::UnsupportedType.into()),
+                None => Err(ErrorKind::IntegerOverflow.into()),
+            },
+            serde_json::Value::String(s) => Ok(Value::from(s)),
+            serde_json::Value::Array(array) => array
+                .into_iter()
+                .enumerate()
+                .map(|(i, json)| Value::try_from(json).map_err(|err| err.in_index(i)))
+                .collect::<Result<_>>()
+                .map(Value::List),
+            serde_json::Value::Object(mut object) if object.len() == 1 => {
+                if let Some(hex) = object.get(HEX) {
+                    return decode_hex(hex).map(Value::Bytes);
+                }
+                match object.remove(DICT) {
+                    Some(serde_json::Value::Array(entries)) => dict_from_entries(entries),
+                    Some(_) => Err(Error::from(ErrorKind::ExpectedArray).in_key(DICT.as_bytes())),
+                    None => dict_from_object(object),
+                }
+            }
+            serde_json::Value::Object(object) => dict_from_object(object),
+        }
+    }
+}
+
+fn bytes_to_json(bytes: Vec<u8>) -> serde_json::Value {
+    match String::from_utf8(bytes) {
+        Ok(s) => serde_json::Value::String(s),
+        Err(err) => {
+            let mut hex = String::with_capacity(err.as_bytes().len() * 2);
+            for byte in err.as_bytes() {
+                write!(hex, "{:02x}", byte).unwrap();
+            }
+            let mut object = Map::new();
+            object.insert(HEX.to_owned(), serde_json::Value::String(hex));
+            serde_json::Value::Object(object)
+        }
+    }
+}
+
+// Decodes a byte string encoded by `bytes_to_json`.
+fn bytes_from_json(json: &serde_json::Value) -> Result<Vec<u8>> {
+    match json {
+        serde_json::Value::String(s) => Ok(s.as_bytes().to_vec()),
+        serde_json::Value::Object(object) if object.len() == 1 && object.contains_key(HEX) => {
+            decode_hex(&object[HEX])
+        }
+        _ => Err(ErrorKind::ExpectedString.into()),
+    }
+}
+
+fn decode_hex(hex: &serde_json::Value) -> Result<Vec<u8>> {
+    let hex = match hex {
+        serde_json::Value::String(hex) if hex.len() % 2 == 0 => hex,
+        _ => return Err(Error::from(ErrorKind::ExpectedString).in_key(HEX.as_bytes())),
+    };
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| ErrorKind::Message(format!("invalid hex string {:?}", hex)).into())
+        })
+        .collect()
+}
+
+fn dict_from_object(object: Map<String, serde_json::Value>) -> Result<Value> {
+    let mut dict = BTreeMap::new();
+    for (key, json) in object {
+        let value = Value::try_from(json).map_err(|err| err.in_key(key.as_bytes()))?;
+        dict.insert(key.into_bytes(), value);
+    }
+    Ok(Value::Dict(dict))
+}
+
+fn dict_from_entries(entries: Vec<serde_json::Value>) -> Result<Value> {
+    let mut dict = BTreeMap::new();
+    for (i, entry) in entries.into_iter().enumerate() {
+        let at_entry = |err: Error| err.in_index(i).in_key(DICT.as_bytes());
+        let (key, json) = match entry {
+            serde_json::Value::Array(mut pair) if pair.len() == 2 => {
+                let json = pair.pop().unwrap();
+                (bytes_from_json(&pair[0]).map_err(at_entry)?, json)
+            }
+            _ => return Err(at_entry(ErrorKind::ExpectedArray.into())),
+        };
+        let value = Value::try_from(json).map_err(|err| err.in_key(&key))?;
+        if dict.insert(key, value).is_some() {
+            return Err(at_entry(ErrorKind::DuplicateKey.into()));
+        }
+    }
+    Ok(Value::Dict(dict))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_json_round_trip() {
+    for data in [
+        &include_bytes!("../../fixtures/single-file.torrent")[..],
+        &include_bytes!("../../fixtures/multi-file.torrent")[..],
+        b"d4:$hex3:abc1:\xffi1ee",
+        b"d4:$hex3:abce",
+        b"d5:$dictle1:ai1ee",
+        b"l0:d4:$hexd4:$hex1:\xfeeee",
+    ] {
+        let value: Value = crate::from_bytes(data).unwrap();
+        let json = serde_json::Value::from(value);
+        let text = serde_json::to_string(&json).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let value = Value::try_from(json).unwrap();
+        assert_eq!(crate::to_bytes(&value).unwrap(), data);
+    }
+}
+
+#[test]
+fn test_to_json() {
+    let value = crate::bencode! {
+        "name" => "a.txt",
+        "hash" => b"\xff\x00",
+        "list" => [1, "x"],
+        "escaped" => { "$hex" => "00" },
+    };
+    assert_eq!(
+        serde_json::Value::from(value),
+        serde_json::json!({
+            "name": "a.txt",
+            "hash": {"$hex": "ff00"},
+            "list": [1, "x"],
+            "escaped": {"$dict": [["$hex", "00"]]},
+        })
+    );
+}
+
+#[test]
+fn test_from_json() {
+    let json = serde_json::json!({
+        "private": true,
+        "files": [{"length": 3}],
+    });
+    assert_eq!(
+        Value::try_from(json).unwrap(),
+        crate::bencode! { "private" => 1, "files" => [{ "length" => 3 }] }
+    );
+
+    let err = Value::try_from(serde_json::json!({"files": [{"length": 1.5}]})).unwrap_err();
+    assert_eq!(err.kind(), &ErrorKind::UnsupportedType);
+    assert_eq!(err.path().as_deref(), Some("files[0].length"));
+    assert_eq!(
+        Value::try_from(serde_json::json!(u64::MAX))
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::IntegerOverflow
+    );
+    assert!(Value::try_from(serde_json::json!({"$hex": "f"})).is_err());
+    assert!(Value::try_from(serde_json::json!({"$hex": "zz"})).is_err());
+    assert_eq!(
+        Value::try_from(serde_json::json!({"$dict": [["a", 1], ["a", 2]]}))
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::DuplicateKey
+    );
+}
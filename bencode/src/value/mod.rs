@@ -0,0 +1,218 @@
+//! A dynamically typed representation of any bencode value.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str;
+
+mod borrowed;
+mod de;
+mod index;
+#[cfg(feature = "json")]
+mod json;
+mod pointer;
+mod pretty;
+mod ser;
+
+pub use borrowed::ValueRef;
+pub use de::from_value;
+pub use ser::to_value;
+
+/// Any valid bencode value.
+///
+/// Dictionary keys are raw byte strings. They are kept in a `BTreeMap` so
+/// iterating over a dictionary yields the keys in the order required by the
+/// canonical encoding.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    /// An integer, `i<digits>e`.
+    Int(i64),
+    /// A byte string, `<length>:<contents>`.
+    Bytes(Vec<u8>),
+    /// A list, `l<values>e`.
+    List(Vec<Value>),
+    /// A dictionary, `d<key><value>...e`.
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    /// Returns the integer if this value is an `Int`.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    /// Returns the raw bytes if this value is a byte string.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if this value is a byte string holding valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|bytes| str::from_utf8(bytes).ok())
+    }
+
+    /// Returns the elements if this value is a `List`.
+    pub fn as_list(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries if this value is a `Dict`.
+    pub fn as_dict(&self) -> Option<&BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Mutable counterpart of [`Value::as_list`].
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    /// Mutable counterpart of [`Value::as_dict`].
+    pub fn as_dict_mut(&mut self) -> Option<&mut BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if this value is a `Dict`.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&Value> {
+        self.as_dict().and_then(|dict| dict.get(key.as_ref()))
+    }
+
+    /// Looks up the element at `index` if this value is a `List`.
+    pub fn get_index(&self, index: usize) -> Option<&Value> {
+        self.as_list().and_then(|list| list.get(index))
+    }
+}
+
+macro_rules! from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(int: $ty) -> Self {
+                    Value::Int(i64::from(int))
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(i8 i16 i32 i64 u8 u16 u32);
+
+// Wider integers only convert when they fit in an `i64`, the range of
+// bencode integers accepted by this crate.
+macro_rules! try_from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl TryFrom<$ty> for Value {
+                type Error = crate::Error;
+
+                fn try_from(int: $ty) -> crate::Result<Self> {
+                    i64::try_from(int)
+                        .map(Value::Int)
+                        .map_err(|_| crate::ErrorKind::IntegerOverflow.into())
+                }
+            }
+        )*
+    };
+}
+
+try_from_integer!(i128 isize u64 u128 usize);
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Int(i64::from(b))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Bytes(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Bytes(s.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(bytes: &[u8]) -> Self {
+        Value::Bytes(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Value {
+    fn from(bytes: &[u8; N]) -> Self {
+        Value::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(bytes: Vec<u8>) -> Self {
+        Value::Bytes(bytes)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(list: Vec<Value>) -> Self {
+        Value::List(list)
+    }
+}
+
+impl From<BTreeMap<Vec<u8>, Value>> for Value {
+    fn from(dict: BTreeMap<Vec<u8>, Value>) -> Self {
+        Value::Dict(dict)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_accessors() {
+    let mut dict = BTreeMap::new();
+    dict.insert(b"length".to_vec(), Value::from(10));
+    dict.insert(b"name".to_vec(), Value::from("file"));
+    dict.insert(b"pieces".to_vec(), Value::from(&b"\xff\xfe"[..]));
+    let value = Value::Dict(dict);
+
+    assert_eq!(value.get("length").and_then(Value::as_int), Some(10));
+    assert_eq!(value.get("name").and_then(Value::as_str), Some("file"));
+    assert_eq!(value.get("pieces").and_then(Value::as_str), None);
+    assert_eq!(
+        value.get(b"pieces").and_then(Value::as_bytes),
+        Some(&b"\xff\xfe"[..])
+    );
+    assert_eq!(value.get("missing"), None);
+    assert_eq!(value.as_list(), None);
+    assert_eq!(value.as_dict().map(BTreeMap::len), Some(3));
+}
+
+#[test]
+fn test_try_from_integer() {
+    assert_eq!(Value::try_from(42_u64).unwrap(), Value::Int(42));
+    assert_eq!(Value::try_from(7_usize).unwrap(), Value::Int(7));
+    assert_eq!(
+        Value::try_from(i64::MAX as u64).unwrap(),
+        Value::Int(i64::MAX)
+    );
+    let err = Value::try_from(i64::MAX as u64 + 1).unwrap_err();
+    assert_eq!(err.kind(), &crate::ErrorKind::IntegerOverflow);
+    assert!(Value::try_from(u128::MAX).is_err());
+}
@@ -0,0 +1,240 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::Value;
+use crate::error::{ErrorKind, Result};
+
+// Splits a JSON Pointer (RFC 6901) such as `/info/files/0/length` into its
+// unescaped segments, or returns `None` if it does not start with `/`. The
+// empty pointer refers to the whole value.
+fn segments(pointer: &str) -> Option<impl Iterator<Item = Cow<'_, str>>> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return None;
+    }
+    Some(pointer.split('/').skip(1).map(|segment| {
+        if segment.contains('~') {
+            Cow::Owned(segment.replace("~1", "/").replace("~0", "~"))
+        } else {
+            Cow::Borrowed(segment)
+        }
+    }))
+}
+
+// Parses a list index, which must not have leading zeros.
+fn parse_index(segment: &str) -> Option<usize> {
+    if segment.starts_with('0') && segment.len() > 1 || segment.starts_with('+') {
+        return None;
+    }
+    segment.parse().ok()
+}
+
+impl Value {
+    fn child(&self, segment: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(dict) => dict.get(segment.as_bytes()),
+            Value::List(list) => list.get(parse_index(segment)?),
+            _ => None,
+        }
+    }
+
+    fn child_mut(&mut self, segment: &str) -> Option<&mut Value> {
+        match self {
+            Value::Dict(dict) => dict.get_mut(segment.as_bytes()),
+            Value::List(list) => list.get_mut(parse_index(segment)?),
+            _ => None,
+        }
+    }
+
+    // Splits off the last segment and looks up the value holding it.
+    fn parent_mut(&mut self, pointer: &str) -> Option<(&mut Value, String)> {
+        let mut segments: Vec<_> = segments(pointer)?.collect();
+        let last = segments.pop()?.into_owned();
+        let parent = segments
+            .iter()
+            .try_fold(self, |value, segment| value.child_mut(segment))?;
+        Some((parent, last))
+    }
+
+    /// Looks up a value by a JSON Pointer such as `/info/files/0/length`,
+    /// where each segment is a dictionary key or a list index. `~1` and `~0`
+    /// stand for `/` and `~` within a key.
+    ///
+    /// ```
+    /// # use bencode::{bencode, Value};
+    /// let value = bencode! { "info" => { "files" => [{ "length" => 3 }] } };
+    /// assert_eq!(value.pointer("/info/files/0/length"), Some(&Value::Int(3)));
+    /// assert_eq!(value.pointer("/info/files/1"), None);
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        segments(pointer)?.try_fold(self, |value, segment| value.child(&segment))
+    }
+
+    /// Mutable counterpart of [`Value::pointer`].
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        segments(pointer)?.try_fold(self, |value, segment| value.child_mut(&segment))
+    }
+
+    /// Like [`Value::pointer`], but a `*` segment matches every entry of a
+    /// dictionary or element of a list. Values are returned in order, and
+    /// paths that do not exist are skipped.
+    ///
+    /// ```
+    /// # use bencode::{bencode, Value};
+    /// let value = bencode! {
+    ///     "files" => [{ "length" => 3 }, { "path" => ["a"] }, { "length" => 5 }],
+    /// };
+    /// let lengths: Vec<_> = value.select("/files/*/length").into_iter().collect();
+    /// assert_eq!(lengths, [&Value::Int(3), &Value::Int(5)]);
+    /// ```
+    pub fn select(&self, pattern: &str) -> Vec<&Value> {
+        let segments = match segments(pattern) {
+            Some(segments) => segments,
+            None => return Vec::new(),
+        };
+        let mut selected = vec![self];
+        for segment in segments {
+            selected = match &*segment {
+                "*" => selected
+                    .into_iter()
+                    .flat_map(|value| -> Box<dyn Iterator<Item = &Value>> {
+                        match value {
+                            Value::Dict(dict) => Box::new(dict.values()),
+                            Value::List(list) => Box::new(list.iter()),
+                            _ => Box::new(core::iter::empty()),
+                        }
+                    })
+                    .collect(),
+                segment => selected
+                    .into_iter()
+                    .filter_map(|value| value.child(segment))
+                    .collect(),
+            };
+        }
+        selected
+    }
+
+    /// Removes the value at `pointer` from its dictionary or list and
+    /// returns it.
+    pub fn remove(&mut self, pointer: &str) -> Option<Value> {
+        let (parent, last) = self.parent_mut(pointer)?;
+        match parent {
+            Value::Dict(dict) => dict.remove(last.as_bytes()),
+            Value::List(list) => {
+                let index = parse_index(&last).filter(|&index| index < list.len())?;
+                Some(list.remove(index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Inserts `value` at `pointer`, whose parent must be an existing
+    /// dictionary or list, and returns the dictionary entry it replaced.
+    ///
+    /// In a list the value is inserted before the element at the index,
+    /// and the index `-` appends it, as in JSON Patch (RFC 6902).
+    ///
+    /// ```
+    /// # use bencode::{bencode, Value};
+    /// let mut value = bencode! { "announce-list" => [["http://a"]] };
+    /// value.insert("/announce-list/0/-", Value::from("http://b")).unwrap();
+    /// value.insert("/comment", Value::from("mirrored")).unwrap();
+    /// assert_eq!(
+    ///     value,
+    ///     bencode! { "announce-list" => [["http://a", "http://b"]], "comment" => "mirrored" }
+    /// );
+    /// ```
+    pub fn insert(&mut self, pointer: &str, value: Value) -> Result<Option<Value>> {
+        let (parent, last) = self.parent_mut(pointer).ok_or(ErrorKind::InvalidPointer)?;
+        match parent {
+            Value::Dict(dict) => Ok(dict.insert(last.into_bytes(), value)),
+            Value::List(list) => {
+                let index = if last == "-" {
+                    list.len()
+                } else {
+                    parse_index(&last)
+                        .filter(|&index| index <= list.len())
+                        .ok_or(ErrorKind::InvalidPointer)?
+                };
+                list.insert(index, value);
+                Ok(None)
+            }
+            _ => Err(ErrorKind::InvalidPointer.into()),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_pointer() {
+    let mut value: Value =
+        crate::from_bytes(include_bytes!("../../fixtures/multi-file.torrent")).unwrap();
+    assert_eq!(value.pointer(""), Some(&value));
+    assert_eq!(
+        value.pointer("/info/files/2/path/1"),
+        Some(&Value::from("part-2.bin"))
+    );
+    assert_eq!(
+        value.pointer("/info/piece length"),
+        Some(&Value::Int(16384))
+    );
+    assert_eq!(value.pointer("info"), None);
+    assert_eq!(value.pointer("/info/files/01"), None);
+    assert_eq!(value.pointer("/info/name/0"), None);
+
+    *value.pointer_mut("/info/files/0/length").unwrap() = Value::Int(1);
+    assert_eq!(value["info"]["files"][0]["length"], Value::Int(1));
+
+    let value = crate::bencode! { "a/b" => { "~" => 1 } };
+    assert_eq!(value.pointer("/a~1b/~0"), Some(&Value::Int(1)));
+}
+
+#[test]
+fn test_select() {
+    let value: Value =
+        crate::from_bytes(include_bytes!("../../fixtures/multi-file.torrent")).unwrap();
+    let lengths: Vec<i64> = value
+        .select("/info/files/*/length")
+        .into_iter()
+        .filter_map(Value::as_int)
+        .collect();
+    assert_eq!(lengths, [1234, 40000, 70001]);
+    assert_eq!(value.select("/info/files/*/path/*").len(), 5);
+    assert_eq!(value.select("/*").len(), value.as_dict().unwrap().len());
+    assert!(value.select("/missing/*").is_empty());
+}
+
+#[test]
+fn test_remove_insert() {
+    let mut value = crate::bencode! {
+        "announce" => "http://a",
+        "list" => [1, 2, 3],
+    };
+    assert_eq!(value.remove("/announce"), Some(Value::from("http://a")));
+    assert_eq!(value.remove("/announce"), None);
+    assert_eq!(value.remove("/list/1"), Some(Value::Int(2)));
+    assert_eq!(value.remove("/list/5"), None);
+    assert_eq!(value.remove(""), None);
+
+    value.insert("/list/0", Value::Int(0)).unwrap();
+    value.insert("/list/-", Value::Int(4)).unwrap();
+    assert_eq!(value["list"], crate::bencode!([0, 1, 3, 4]));
+    assert_eq!(
+        value.insert("/list", Value::Int(5)).unwrap(),
+        Some(crate::bencode!([0, 1, 3, 4]))
+    );
+    assert_eq!(
+        value
+            .insert("/missing/key", Value::Int(1))
+            .unwrap_err()
+            .kind(),
+        &ErrorKind::InvalidPointer
+    );
+    assert_eq!(
+        value.insert("/list/0", Value::Int(1)).unwrap_err().kind(),
+        &ErrorKind::InvalidPointer
+    );
+}
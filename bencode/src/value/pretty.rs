@@ -0,0 +1,154 @@
+use core::fmt::{self, Display, Write};
+use core::str;
+
+use super::Value;
+
+// SHA-1 digests are 20 bytes, and the `pieces` string of a torrent is their
+// concatenation.
+const PIECE_HASH_LEN: usize = 20;
+
+/// Renders the value as JSON-like text for debugging.
+///
+/// Byte strings holding printable UTF-8 are shown quoted, other byte
+/// strings as `<hex ...>`, and the `pieces` string of a torrent as the
+/// number of piece hashes it holds. The alternate form, `{:#}`, indents
+/// nested dictionaries and lists.
+///
+/// ```
+/// # use bencode::bencode;
+/// let value = bencode! { "name" => "a.txt", "hash" => b"\xff\x00", "files" => [] };
+/// assert_eq!(
+///     value.to_string(),
+///     r#"{"files": [], "hash": <hex ff00>, "name": "a.txt"}"#
+/// );
+/// ```
+impl Display for Value {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let indent = if formatter.alternate() { Some(0) } else { None };
+        write_value(formatter, self, None, indent)
+    }
+}
+
+fn write_value(
+    out: &mut fmt::Formatter,
+    value: &Value,
+    key: Option<&[u8]>,
+    indent: Option<usize>,
+) -> fmt::Result {
+    match value {
+        Value::Int(int) => write!(out, "{}", int),
+        Value::Bytes(bytes)
+            if key == Some(b"pieces") && !bytes.is_empty() && bytes.len() % PIECE_HASH_LEN == 0 =>
+        {
+            write!(out, "<{} piece hashes>", bytes.len() / PIECE_HASH_LEN)
+        }
+        Value::Bytes(bytes) => write_bytes(out, bytes),
+        Value::List(list) if list.is_empty() => out.write_str("[]"),
+        Value::List(list) => {
+            out.write_char('[')?;
+            for (i, element) in list.iter().enumerate() {
+                write_separator(out, i, indent.map(|indent| indent + 1))?;
+                write_value(out, element, None, indent.map(|indent| indent + 1))?;
+            }
+            write_close(out, ']', indent)
+        }
+        Value::Dict(dict) if dict.is_empty() => out.write_str("{}"),
+        Value::Dict(dict) => {
+            out.write_char('{')?;
+            for (i, (key, value)) in dict.iter().enumerate() {
+                write_separator(out, i, indent.map(|indent| indent + 1))?;
+                write_bytes(out, key)?;
+                out.write_str(": ")?;
+                write_value(out, value, Some(key), indent.map(|indent| indent + 1))?;
+            }
+            write_close(out, '}', indent)
+        }
+    }
+}
+
+fn write_bytes(out: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    match str::from_utf8(bytes) {
+        Ok(s) if !s.chars().any(char::is_control) => write!(out, "{:?}", s),
+        _ => {
+            out.write_str("<hex ")?;
+            for byte in bytes {
+                write!(out, "{:02x}", byte)?;
+            }
+            out.write_char('>')
+        }
+    }
+}
+
+// Starts the `i`th element of a list or dictionary.
+fn write_separator(out: &mut fmt::Formatter, i: usize, indent: Option<usize>) -> fmt::Result {
+    if i > 0 {
+        out.write_char(',')?;
+    }
+    match indent {
+        Some(indent) => write!(out, "\n{:1$}", "", indent * 2),
+        None if i > 0 => out.write_char(' '),
+        None => Ok(()),
+    }
+}
+
+fn write_close(out: &mut fmt::Formatter, close: char, indent: Option<usize>) -> fmt::Result {
+    if let Some(indent) = indent {
+        write!(out, "\n{:1$}", "", indent * 2)?;
+    }
+    out.write_char(close)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_pretty() {
+    let value: Value =
+        crate::from_bytes(include_bytes!("../../fixtures/multi-file.torrent")).unwrap();
+    let expected = r#"{
+  "announce": "http://tracker.example.org:6969/announce",
+  "created by": "fixture-gen",
+  "creation date": 1665000000,
+  "info": {
+    "files": [
+      {
+        "length": 1234,
+        "path": [
+          "README.txt"
+        ]
+      },
+      {
+        "length": 40000,
+        "path": [
+          "data",
+          "part-1.bin"
+        ]
+      },
+      {
+        "length": 70001,
+        "path": [
+          "data",
+          "part-2.bin"
+        ]
+      }
+    ],
+    "name": "multi",
+    "piece length": 16384,
+    "pieces": <7 piece hashes>,
+    "private": 1,
+    "source": "fixture"
+  }
+}"#;
+    assert_eq!(format!("{:#}", value), expected);
+}
+
+#[test]
+fn test_display_bytes() {
+    assert_eq!(Value::from("a\"b").to_string(), r#""a\"b""#);
+    assert_eq!(Value::from("\n").to_string(), "<hex 0a>");
+    assert_eq!(Value::from(&b"\xff\x00"[..]).to_string(), "<hex ff00>");
+    assert_eq!(Value::from(vec![Value::Int(1)]).to_string(), "[1]");
+    assert_eq!(
+        crate::bencode! { "pieces" => (vec![0xffu8; 21]) }.to_string(),
+        format!("{{\"pieces\": <hex {}>}}", "ff".repeat(21))
+    );
+}
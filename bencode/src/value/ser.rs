@@ -0,0 +1,485 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use serde::ser;
+use serde::Serialize;
+use serde_bytes::Bytes;
+
+use super::Value;
+use crate::error::{Error, ErrorKind, Result};
+use crate::raw;
+use crate::ser::KeySerializer;
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            Value::Int(int) => serializer.serialize_i64(*int),
+            Value::Bytes(bytes) => serializer.serialize_bytes(bytes),
+            Value::List(list) => list.serialize(serializer),
+            Value::Dict(dict) => {
+                use serde::ser::SerializeMap;
+                let mut map = serializer.serialize_map(Some(dict.len()))?;
+                for (key, value) in dict {
+                    map.serialize_entry(Bytes::new(key), value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Converts any `T: Serialize` into a [`Value`].
+///
+/// The mapping is the same as the one used by [`to_bytes`](crate::to_bytes),
+/// duplicate dictionary keys included, which are an
+/// [`ErrorKind::DuplicateKey`] error. `to_bytes(&to_value(&x)?)` therefore
+/// produces the same output as `to_bytes(&x)`, except that:
+///
+/// - integers outside the range of `i64`, such as `u64::MAX`, are an
+///   [`ErrorKind::IntegerOverflow`] error, since [`Value::Int`] holds an
+///   `i64`;
+/// - a [`RawValue`](crate::RawValue) is decoded, so its bytes are encoded
+///   again rather than copied, with dictionary keys sorted.
+pub fn to_value<T>(value: &T) -> Result<Value>
+where
+    T: ?Sized + Serialize,
+{
+    value
+        .serialize(Serializer)?
+        .ok_or_else(|| ErrorKind::NoneNotInDict.into())
+}
+
+// Serializes into a `Value`. `None` is represented as `Ok(None)` so that
+// dictionaries can drop the entry, while lists and the top level reject it.
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeDict;
+    type SerializeStruct = SerializeDict;
+    type SerializeStructVariant = SerializeVariant<SerializeDict>;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Value>> {
+        Ok(Some(Value::from(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<Value>> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<Value>> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<Value>> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Value>> {
+        Ok(Some(Value::Int(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Option<Value>> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<Value>> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<Value>> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Value>> {
+        let v = i64::try_from(v).map_err(|_| ErrorKind::IntegerOverflow)?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Option<Value>> {
+        Err(ErrorKind::UnsupportedType.into())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Option<Value>> {
+        Err(ErrorKind::UnsupportedType.into())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Value>> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Value>> {
+        Ok(Some(Value::from(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Option<Value>> {
+        Ok(Some(Value::from(v)))
+    }
+
+    fn serialize_none(self) -> Result<Option<Value>> {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Value>> {
+        Ok(Some(Value::Bytes(Vec::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<Value>> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Option<Value>> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+    {
+        if name == raw::TOKEN {
+            let raw = value.serialize(KeySerializer)?;
+            return crate::from_bytes(&raw).map(Some);
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut dict = BTreeMap::new();
+        dict.insert(variant.as_bytes().to_vec(), to_value(value)?);
+        Ok(Some(Value::Dict(dict)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeList>> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeDict> {
+        Ok(SerializeDict {
+            dict: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeDict> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeDict>> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeList {
+    list: Vec<Value>,
+}
+
+impl SerializeList {
+    fn push<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.list.push(to_value(value)?);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        Ok(Some(Value::List(self.list)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeDict {
+    dict: BTreeMap<Vec<u8>, Value>,
+    // The key passed to `serialize_key`, waiting for its value.
+    key: Option<Vec<u8>>,
+}
+
+impl SerializeDict {
+    fn insert<T>(&mut self, key: Vec<u8>, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        // `None` values are left out of the dictionary altogether.
+        if let Some(value) = value.serialize(Serializer)? {
+            if self.dict.insert(key, value).is_some() {
+                return Err(ErrorKind::DuplicateKey.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeDict {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        Ok(Some(Value::Dict(self.dict)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeDict {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.insert(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+// Wraps the contents of a tuple or struct variant in a single-key dictionary
+// named after the variant.
+struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &'static str, value: Option<Value>) -> Result<Option<Value>> {
+        let mut dict = BTreeMap::new();
+        dict.insert(
+            variant.as_bytes().to_vec(),
+            value.ok_or(ErrorKind::NoneNotInDict)?,
+        );
+        Ok(Some(Value::Dict(dict)))
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        Self::wrap(self.variant, ser::SerializeSeq::end(self.inner)?)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeDict> {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.insert(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        Self::wrap(self.variant, ser::SerializeMap::end(self.inner)?)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_to_value() {
+    #[derive(Serialize)]
+    struct File {
+        length: u32,
+        path: Vec<&'static str>,
+        md5sum: Option<&'static str>,
+    }
+
+    let file = File {
+        length: 3,
+        path: vec!["dir", "a.txt"],
+        md5sum: None,
+    };
+    let mut expected = BTreeMap::new();
+    expected.insert(b"length".to_vec(), Value::Int(3));
+    expected.insert(
+        b"path".to_vec(),
+        Value::List(vec![Value::from("dir"), Value::from("a.txt")]),
+    );
+    assert_eq!(to_value(&file).unwrap(), Value::Dict(expected));
+}
+
+#[test]
+fn test_to_value_errors() {
+    let mut map = BTreeMap::new();
+    map.insert(true, "one");
+    assert_eq!(
+        to_value(&map).unwrap_err().kind(),
+        &ErrorKind::KeyMustBeString
+    );
+    assert_eq!(
+        to_value(&1.5).unwrap_err().kind(),
+        &ErrorKind::UnsupportedType
+    );
+    assert_eq!(
+        to_value(&None::<u8>).unwrap_err().kind(),
+        &ErrorKind::NoneNotInDict
+    );
+    assert_eq!(
+        to_value(&u64::MAX).unwrap_err().kind(),
+        &ErrorKind::IntegerOverflow
+    );
+
+    // Duplicate keys are rejected, like `to_bytes` does.
+    struct Entries<'a>(&'a [(&'a str, i64)]);
+
+    impl Serialize for Entries<'_> {
+        fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+        {
+            serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+        }
+    }
+
+    let entries = Entries(&[("a", 1), ("a", 2)]);
+    assert_eq!(
+        to_value(&entries).unwrap_err().kind(),
+        &ErrorKind::DuplicateKey
+    );
+    assert_eq!(
+        crate::to_bytes(&entries).unwrap_err().kind(),
+        &ErrorKind::DuplicateKey
+    );
+}
+
+#[test]
+fn test_value_round_trip() {
+    let input = b"d8:announce3:url4:infod6:lengthi-7e6:pieces2:\xff\x00ee";
+    let value: Value = crate::from_bytes(input).unwrap();
+    assert_eq!(crate::to_bytes(&value).unwrap(), input);
+}
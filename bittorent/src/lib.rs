@@ -44,7 +44,7 @@ impl Peer {
     pub fn establish_handshake(&mut self, peer_id: &str) {
         // Send handhake message
         self.stream
-            .write(&Handshake::new(self.metainfo.get_info_hash(), peer_id).serialize())
+            .write_all(&Handshake::new(self.metainfo.get_info_hash(), peer_id).serialize())
             .unwrap();
 
         // Receive handshake response
@@ -57,22 +57,28 @@ impl Peer {
         let _unchoke = Message::from_stream(self.stream.try_clone().unwrap());
     }
 
-    pub async fn download_piece(&mut self, index: usize) {
+    pub async fn download_piece(&mut self, index: usize) -> Result<(), String> {
+        // Checked before anything is sent to the peer.
+        let hash = *self
+            .metainfo
+            .info
+            .piece_hash(index)
+            .ok_or_else(|| format!("No piece at index {}", index))?;
+        // Piece indices are 32 bits on the wire.
+        let piece_index = u32::try_from(index)
+            .map_err(|_| format!("Piece index {} does not fit in 32 bits", index))?;
         let mut buff: Vec<u8> = Vec::new();
-        let mut offset = 0;
-        let hash_list: Vec<&[u8]> = self.metainfo.info.pieces.chunks(20).collect();
 
         self.stream
-            .write(&Message::interested().serialize())
+            .write_all(&Message::interested().serialize())
             .unwrap();
 
         let _unchoke = Message::from_stream(self.stream.try_clone().unwrap());
 
-        while offset < self.metainfo.info.piece_length {
-            let length = 2_u32.pow(14);
+        for (offset, length) in self.metainfo.info.blocks_in_piece(index, 2_u32.pow(14)) {
             // Send request
             self.stream
-                .write(&Message::request(index as u32, offset, length).serialize())
+                .write_all(&Message::request(piece_index, offset, length).serialize())
                 .unwrap();
 
             // Receive piece
@@ -81,28 +87,28 @@ impl Peer {
             let p_offset = u32::from_be_bytes(piece.payload.get(4..8).unwrap().try_into().unwrap());
             let p_data = piece.payload.get(8..).unwrap();
             buff.append(&mut p_data.to_vec());
-            offset = offset + length;
             println!("Received piece {} at offset {}", p_index, p_offset);
         }
         // Verify the piece checksum
-        if !is_checksum_correct(buff, hash_list[index]) {
+        if !is_checksum_correct(buff, &hash) {
             println!("INVALID CHEKSUM")
         }
         // Send we have the piece
         self.stream
-            .write(&Message::have(index as u32).serialize())
+            .write_all(&Message::have(piece_index).serialize())
             .unwrap();
+        Ok(())
     }
 }
 
 fn is_checksum_correct(buff: Vec<u8>, hash: &[u8]) -> bool {
     let mut hasher = Sha1::new();
-    hasher.update(&buff);
+    hasher.update(buff);
     let result = hasher.finalize();
     result[..] == hash[..]
 }
 
-fn _has_piece(bf: &Vec<u8>, index: u32) -> bool {
+fn _has_piece(bf: &[u8], index: u32) -> bool {
     let bytes_index = index / 8;
     let offset = index % 8;
     let val = bf[bytes_index as usize] >> (7 - offset) & 1;
@@ -113,22 +119,22 @@ fn _has_piece(bf: &Vec<u8>, index: u32) -> bool {
 fn test_parsing_metainfo() {
     let data =
         std::fs::read("debian-11.5.0-amd64-netinst.iso.torrent").expect("Unable to read file");
-    let deserialized = Metainfo::from_bytes(&data);
+    let deserialized = Metainfo::from_bytes(&data).unwrap();
     assert_eq!(
-        deserialized.announce,
-        "http://bttracker.debian.org:6969/announce"
+        deserialized.announce.as_deref(),
+        Some("http://bttracker.debian.org:6969/announce")
     );
     assert_eq!(deserialized.info.name, "debian-11.5.0-amd64-netinst.iso");
     assert_eq!(deserialized.info.piece_length, 262144);
-    assert_eq!(deserialized.info.length, 400556032);
+    assert_eq!(deserialized.info.length, Some(400556032));
 }
 
 #[test]
 fn test_get_peers() {
     let data =
         std::fs::read("debian-11.5.0-amd64-netinst.iso.torrent").expect("Unable to read file");
-    let deserialized = Metainfo::from_bytes(&data);
-    let _peers = deserialized.get_peers();
+    let deserialized = Metainfo::from_bytes(&data).unwrap();
+    let _peers = deserialized.get_peers().unwrap();
 }
 
 #[tokio::test]
@@ -143,13 +149,13 @@ async fn test_connecting_to_peers() {
 
 
     for peer in peers {
-        let metainfo = Metainfo::from_bytes(&data);
-        let n_piece = metainfo.info.pieces.chunks(20).len();
+        let metainfo = Metainfo::from_bytes(&data).unwrap();
+        let n_piece = metainfo.info.piece_count();
         let handle = tokio::spawn(async move {
             let mut peer = Peer::establish_connection(peer, metainfo.clone()).unwrap();
             peer.establish_handshake(peer_id);
             for piece in 0..n_piece {
-                peer.download_piece(piece).await;
+                peer.download_piece(piece).await.unwrap();
             }
         });
         handle.await.unwrap();
@@ -1,10 +1,12 @@
 //! MetaInfo module
+use crate::tracker;
 use crate::utils::urlencode;
+use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
-use url::Url;
+use std::fmt;
 use std::net::{Ipv4Addr, SocketAddrV4};
-use serde::{Deserialize, Serialize};
-use crate::tracker;
+use std::path::PathBuf;
+use url::Url;
 
 /// Informations about the Torrent
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -16,8 +18,18 @@ pub struct Info {
     #[serde(rename = "piece length")]
     pub piece_length: u32,
 
-    /// Length of the file in bytes.
-    pub length: u32,
+    /// Length of the file in bytes, in single-file mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<u64>,
+
+    /// MD5 sum of the file, in single-file mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md5sum: Option<String>,
+
+    /// Files of the torrent, in multi-file mode. `name` is then
+    /// the name of the directory they are saved in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<File>>,
 
     /// String whose length is a multiple of 20.
     /// It is to be subdivided into strings of length 20
@@ -27,70 +39,1212 @@ pub struct Info {
     pub pieces: Vec<u8>,
 }
 
+impl Info {
+    /// Iterates over the files of the torrent, in the order their
+    /// contents appear in the concatenated torrent data.
+    ///
+    /// Iteration stops after a file that ends beyond `u64::MAX` bytes,
+    /// which [`Metainfo::from_bytes`] rules out.
+    pub fn file_entries(&self) -> FileEntries<'_> {
+        FileEntries {
+            info: self,
+            index: 0,
+            offset: Some(0),
+        }
+    }
+
+    /// Total number of bytes of the torrent, all files included, or `None`
+    /// if it does not fit in a `u64`, which [`Metainfo::from_bytes`] rules
+    /// out.
+    pub fn total_length(&self) -> Option<u64> {
+        self.file_entries()
+            .try_fold(0u64, |total, file| total.checked_add(file.length))
+    }
+
+    /// Number of pieces the torrent data is split into.
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len() / 20
+    }
+
+    /// SHA1 hash of the piece at `index`.
+    pub fn piece_hash(&self, index: usize) -> Option<&[u8; 20]> {
+        self.pieces.chunks_exact(20).nth(index)?.try_into().ok()
+    }
+
+    /// Number of bytes in the piece at `index`.
+    ///
+    /// Every piece is `piece_length` long except the last one,
+    /// which holds whatever remains of the torrent data. A piece that does
+    /// not exist, or lies beyond `u64::MAX` bytes, has no size.
+    pub fn piece_size(&self, index: usize) -> Option<u32> {
+        let count = self.piece_count();
+        if index >= count {
+            return None;
+        }
+        // Only the last piece needs the total length, which takes a pass
+        // over every file.
+        if index + 1 < count {
+            return Some(self.piece_length);
+        }
+        let remaining = self
+            .total_length()?
+            .saturating_sub(self.piece_offset(index)?);
+        Some(remaining.min(u64::from(self.piece_length)) as u32)
+    }
+
+    /// Splits the piece at `index` into blocks of at most `block_size`
+    /// bytes, given as `(begin, length)` pairs relative to the piece.
+    /// A piece that does not exist has no blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is zero.
+    pub fn blocks_in_piece(&self, index: usize, block_size: u32) -> Blocks {
+        assert!(block_size > 0, "block size must be positive");
+        Blocks {
+            begin: 0,
+            end: self.piece_size(index).unwrap_or(0),
+            block_size,
+        }
+    }
+
+    /// Lists the parts of files the piece at `index` is made of, in order.
+    /// A piece that does not exist spans no file.
+    pub fn piece_files(&self, index: usize) -> Vec<FileSegment<'_>> {
+        if index >= self.piece_count() {
+            return Vec::new();
+        }
+        // The files end where the last piece does, so there is no need to
+        // work out its size from the total length first.
+        let start = match self.piece_offset(index) {
+            Some(start) => start,
+            None => return Vec::new(),
+        };
+        let end = start.saturating_add(u64::from(self.piece_length));
+
+        let mut segments = Vec::new();
+        for file in self.file_entries() {
+            let file_end = file.offset.saturating_add(file.length);
+            if file_end <= start || file.length == 0 {
+                continue;
+            }
+            if file.offset >= end {
+                break;
+            }
+            let segment_start = start.max(file.offset);
+            let segment_end = end.min(file_end);
+            segments.push(FileSegment {
+                offset: segment_start - file.offset,
+                length: (segment_end - segment_start) as u32,
+                file,
+            });
+        }
+        segments
+    }
+
+    /// Position of the first byte of the piece at `index` within the
+    /// concatenated torrent data, if it fits in a `u64`.
+    fn piece_offset(&self, index: usize) -> Option<u64> {
+        u64::try_from(index)
+            .ok()?
+            .checked_mul(u64::from(self.piece_length))
+    }
+}
+
+/// A file of a multi-file torrent.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct File {
+    /// Length of the file in bytes.
+    pub length: u64,
+
+    /// Path of the file relative to the torrent directory,
+    /// one element per directory and the file name last.
+    pub path: Vec<String>,
+
+    /// MD5 sum of the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md5sum: Option<String>,
+}
+
+/// A file of a torrent, whatever its mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry<'a> {
+    /// Path the file is saved at, relative to the download directory.
+    pub path: PathBuf,
+
+    /// Length of the file in bytes.
+    pub length: u64,
+
+    /// Position of the first byte of the file within the
+    /// concatenated torrent data.
+    pub offset: u64,
+
+    /// MD5 sum of the file, if the torrent provides one.
+    pub md5sum: Option<&'a str>,
+}
+
+/// Iterator over the files of a torrent, see [`Info::file_entries`].
+#[derive(Debug, Clone)]
+pub struct FileEntries<'a> {
+    info: &'a Info,
+    index: usize,
+    // Offset of the next file, or `None` once it no longer fits in a `u64`.
+    offset: Option<u64>,
+}
+
+impl<'a> Iterator for FileEntries<'a> {
+    type Item = FileEntry<'a>;
+
+    fn next(&mut self) -> Option<FileEntry<'a>> {
+        let offset = self.offset?;
+        let entry = match &self.info.files {
+            Some(files) => {
+                let file = files.get(self.index)?;
+                let mut path = PathBuf::from(&self.info.name);
+                path.extend(&file.path);
+                FileEntry {
+                    path,
+                    length: file.length,
+                    offset,
+                    md5sum: file.md5sum.as_deref(),
+                }
+            }
+            None if self.index == 0 => FileEntry {
+                path: PathBuf::from(&self.info.name),
+                length: self.info.length?,
+                offset: 0,
+                md5sum: self.info.md5sum.as_deref(),
+            },
+            None => return None,
+        };
+        self.index += 1;
+        self.offset = offset.checked_add(entry.length);
+        Some(entry)
+    }
+}
+
+/// Part of a file covered by a piece, see [`Info::piece_files`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSegment<'a> {
+    /// The file the bytes belong to.
+    pub file: FileEntry<'a>,
+
+    /// Position of the first byte within the file.
+    pub offset: u64,
+
+    /// Number of bytes.
+    pub length: u32,
+}
+
+/// Blocks of a piece, see [`Info::blocks_in_piece`].
+#[derive(Debug, Clone)]
+pub struct Blocks {
+    begin: u32,
+    end: u32,
+    block_size: u32,
+}
+
+impl Iterator for Blocks {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<(u32, u32)> {
+        if self.begin >= self.end {
+            return None;
+        }
+        let begin = self.begin;
+        let length = self.block_size.min(self.end - begin);
+        self.begin += length;
+        Some((begin, length))
+    }
+}
+
 /// Metainfo files (also known as .torrent files)
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Metainfo {
-    /// The URL of the tracker.
-    pub announce: String,
+    /// The URL of the tracker. Ignored when `announce_list` is present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announce: Option<String>,
+
+    /// Tiers of tracker URLs, tried in order (BEP 12).
+    #[serde(
+        rename = "announce-list",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub announce_list: Vec<Vec<String>>,
 
     /// This maps to a Info struct.
     pub info: Info,
+
+    /// SHA1 hash of the `info` dictionary exactly as it appears in the
+    /// torrent file, set by [`Metainfo::from_bytes`].
+    #[serde(skip)]
+    info_hash: Option<[u8; 20]>,
 }
 
-impl Metainfo {
-    pub fn from_bytes(bytes: &[u8]) -> Self {
-        bendy::serde::from_bytes::<Metainfo>(&bytes).expect("Failed to deserialize torrent file")
+/// Reasons a torrent file is rejected by [`Metainfo::from_bytes`].
+#[derive(Debug)]
+pub enum MetainfoError {
+    /// The file is not a bencoded metainfo dictionary.
+    Decode(bencode::Error),
+
+    /// An announce URL cannot be parsed.
+    InvalidAnnounceUrl(String, url::ParseError),
+
+    /// `name` is empty.
+    EmptyName,
+
+    /// `info` has neither `length` nor `files`.
+    MissingLength,
+
+    /// `info` has both `length` and `files`.
+    LengthAndFiles,
+
+    /// The total length of the files does not fit in a `u64`.
+    LengthOverflow,
+
+    /// `piece length` is zero or not a power of two.
+    InvalidPieceLength(u32),
+
+    /// The length of `pieces` is not a multiple of 20.
+    InvalidPiecesLength(usize),
+
+    /// The number of piece hashes does not match the total length.
+    PieceCountMismatch {
+        /// Number of pieces needed to hold the files.
+        expected: u64,
+        /// Number of hashes in `pieces`.
+        actual: usize,
+    },
+
+    /// A file path has no segment.
+    EmptyPath,
+
+    /// A file path has an empty segment.
+    EmptyPathSegment(Vec<String>),
+
+    /// A file path has a `.` segment.
+    CurrentDirectory(Vec<String>),
+
+    /// A file path has a `..` segment.
+    ParentDirectory(Vec<String>),
+
+    /// A file path has a segment that is an absolute path.
+    AbsolutePath(Vec<String>),
+
+    /// A file path has a segment containing `/` or `\`.
+    PathSeparator(Vec<String>),
+}
+
+impl fmt::Display for MetainfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetainfoError::Decode(err) => write!(f, "invalid torrent file: {}", err),
+            MetainfoError::InvalidAnnounceUrl(url, err) => {
+                write!(f, "invalid announce url {:?}: {}", url, err)
+            }
+            MetainfoError::EmptyName => f.write_str("empty name"),
+            MetainfoError::MissingLength => f.write_str("info has neither length nor files"),
+            MetainfoError::LengthAndFiles => f.write_str("info has both length and files"),
+            MetainfoError::LengthOverflow => f.write_str("total length does not fit in 64 bits"),
+            MetainfoError::InvalidPieceLength(length) => {
+                write!(f, "piece length {} is not a power of two", length)
+            }
+            MetainfoError::InvalidPiecesLength(length) => {
+                write!(f, "pieces length {} is not a multiple of 20", length)
+            }
+            MetainfoError::PieceCountMismatch { expected, actual } => {
+                write!(f, "expected {} pieces, found {}", expected, actual)
+            }
+            MetainfoError::EmptyPath => f.write_str("empty file path"),
+            MetainfoError::EmptyPathSegment(path) => {
+                write!(f, "empty segment in file path {:?}", path)
+            }
+            MetainfoError::CurrentDirectory(path) => {
+                write!(f, "current directory in file path {:?}", path)
+            }
+            MetainfoError::ParentDirectory(path) => {
+                write!(f, "parent directory in file path {:?}", path)
+            }
+            MetainfoError::AbsolutePath(path) => write!(f, "absolute file path {:?}", path),
+            MetainfoError::PathSeparator(path) => {
+                write!(f, "separator in file path segment {:?}", path)
+            }
+        }
     }
+}
 
-    pub fn get_info_hash(&self) -> [u8; 20] {
-        let mut hasher = Sha1::new();
-        let bencoded_info = bendy::serde::to_bytes(&self.info).expect("Failed to encode info");
-        hasher.update(bencoded_info);
-        hasher
-            .finalize()
-            .try_into()
-            .expect("hash must be of size 20")
-    }
-
-    pub fn get_peers(&self) -> Vec<SocketAddrV4> {
-        let info_hash = urlencode(&self.get_info_hash());
-
-        let mut url = Url::parse(&self.announce).expect("Not a valid announce url");
-        url.set_query(Some(&format!("info_hash={}", info_hash)));
-
-        let payload = tracker::Request {
-            peer_id: "-DE203s-x49Ta1Q*sgGQ",
-            port: 58438,
-            uploaded: 0,
-            downloaded: 0,
-            left: self.info.length,
-            compact: 1,
+impl std::error::Error for MetainfoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MetainfoError::Decode(err) => Some(err),
+            MetainfoError::InvalidAnnounceUrl(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<bencode::Error> for MetainfoError {
+    fn from(err: bencode::Error) -> Self {
+        MetainfoError::Decode(err)
+    }
+}
+
+/// Checks that a file path stays within the torrent directory.
+///
+/// The checks do not depend on the platform, so that a torrent is accepted
+/// or rejected the same way everywhere.
+fn validate_path(path: &[String]) -> Result<(), MetainfoError> {
+    if path.is_empty() {
+        return Err(MetainfoError::EmptyPath);
+    }
+    for segment in path {
+        let error = match segment.as_str() {
+            "" => MetainfoError::EmptyPathSegment,
+            "." => MetainfoError::CurrentDirectory,
+            ".." => MetainfoError::ParentDirectory,
+            _ if segment.starts_with(['/', '\\']) || has_drive_prefix(segment) => {
+                MetainfoError::AbsolutePath
+            }
+            _ if segment.contains(['/', '\\']) => MetainfoError::PathSeparator,
+            _ => continue,
         };
+        return Err(error(path.to_vec()));
+    }
+    Ok(())
+}
 
-        let client = reqwest::blocking::Client::new();
-        let res = match client.get(url).query(&payload).send() {
-            Ok(r) => r.bytes(),
-            Err(e) => panic!("Failed to establish connection to tracker: {}", e),
+/// Whether `segment` starts like a Windows drive, such as `C:`.
+fn has_drive_prefix(segment: &str) -> bool {
+    let bytes = segment.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// A torrent file whose `info` dictionary is borrowed as encoded, so it can
+/// be hashed as is and then deserialized from the same bytes.
+#[derive(Deserialize)]
+struct RawMetainfo<'a> {
+    #[serde(default)]
+    announce: Option<String>,
+    #[serde(rename = "announce-list", default)]
+    announce_list: Vec<Vec<String>>,
+    #[serde(borrow)]
+    info: bencode::RawValue<'a>,
+}
+
+impl Metainfo {
+    /// Parses and validates a torrent file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MetainfoError> {
+        let raw = bencode::from_bytes::<RawMetainfo>(bytes)?;
+        let info_bytes = raw.info.as_bytes();
+        // `info_bytes` borrows from `bytes`, so errors in it can be reported
+        // at their offset in the whole file.
+        let info_start = info_bytes.as_ptr() as usize - bytes.as_ptr() as usize;
+        let info =
+            bencode::from_bytes(info_bytes).map_err(|err| err.within(info_start, b"info"))?;
+        let metainfo = Metainfo {
+            announce: raw.announce,
+            announce_list: raw.announce_list,
+            info,
+            info_hash: Some(Sha1::digest(info_bytes).into()),
         };
+        metainfo.validate()?;
+        Ok(metainfo)
+    }
 
-        let input = res.unwrap();
-        let de_res = match bendy::serde::from_bytes::<tracker::Response>(&input) {
-            Ok(v) => v,
-            Err(_) => panic!("Failed to deserialize tracker response"),
+    fn validate(&self) -> Result<(), MetainfoError> {
+        // Trackerless torrents, which find peers through the DHT, have no
+        // tiers at all.
+        for url in self.tracker_tiers().iter().flatten() {
+            if let Err(err) = Url::parse(url) {
+                return Err(MetainfoError::InvalidAnnounceUrl(url.clone(), err));
+            }
+        }
+
+        let info = &self.info;
+        if info.name.is_empty() {
+            return Err(MetainfoError::EmptyName);
+        }
+        validate_path(std::slice::from_ref(&info.name))?;
+
+        let total_length = match (info.length, &info.files) {
+            (Some(length), None) => length,
+            (None, Some(files)) => {
+                let mut total: u64 = 0;
+                for file in files {
+                    validate_path(&file.path)?;
+                    total = total
+                        .checked_add(file.length)
+                        .ok_or(MetainfoError::LengthOverflow)?;
+                }
+                total
+            }
+            (None, None) => return Err(MetainfoError::MissingLength),
+            (Some(_), Some(_)) => return Err(MetainfoError::LengthAndFiles),
         };
 
-        let chunked_peers = de_res.peers.chunks_exact(6);
-        let mut peers: Vec<SocketAddrV4> = Vec::new();
-        for peer in chunked_peers {
-            let ip: [u8; 4] = peer[..4].try_into().unwrap();
-            let ip = Ipv4Addr::from(ip);
+        if !info.piece_length.is_power_of_two() {
+            return Err(MetainfoError::InvalidPieceLength(info.piece_length));
+        }
+        if !info.pieces.len().is_multiple_of(20) {
+            return Err(MetainfoError::InvalidPiecesLength(info.pieces.len()));
+        }
+        let expected = total_length.div_ceil(u64::from(info.piece_length));
+        if expected != info.piece_count() as u64 {
+            return Err(MetainfoError::PieceCountMismatch {
+                expected,
+                actual: info.piece_count(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the SHA1 hash identifying the torrent.
+    ///
+    /// The hash covers the `info` dictionary as it was read, keys this crate
+    /// does not know about included. A `Metainfo` that did not come from
+    /// [`Metainfo::from_bytes`] has its `info` re-encoded instead.
+    pub fn get_info_hash(&self) -> [u8; 20] {
+        match self.info_hash {
+            Some(info_hash) => info_hash,
+            None => {
+                let bencoded_info = bencode::to_bytes(&self.info).expect("Failed to encode info");
+                Sha1::digest(bencoded_info).into()
+            }
+        }
+    }
+
+    /// Asks the trackers for peers, see [`Announcer::get_peers`].
+    ///
+    /// The order in which trackers answered is forgotten afterwards. Keep an
+    /// [`Announcer`] around to announce to the same trackers repeatedly.
+    pub fn get_peers(&self) -> Result<Vec<SocketAddrV4>, AnnounceError> {
+        Announcer::new(self).get_peers()
+    }
+
+    /// Returns the tiers of trackers to announce to, without the empty ones.
+    ///
+    /// Clients supporting BEP 12 ignore `announce` when there is an
+    /// `announce-list`, so it only makes up a tier of its own otherwise.
+    pub fn tracker_tiers(&self) -> Vec<Vec<String>> {
+        let tiers: Vec<Vec<String>> = self
+            .announce_list
+            .iter()
+            .filter(|tier| !tier.is_empty())
+            .cloned()
+            .collect();
+        if !tiers.is_empty() {
+            return tiers;
+        }
+        self.announce.iter().map(|url| vec![url.clone()]).collect()
+    }
+}
+
+/// The trackers of a torrent and the order they are tried in, following
+/// the tiers of BEP 12.
+///
+/// Trackers are shuffled within their tier when the announcer is created,
+/// then tried tier after tier. The first one to answer is moved to the
+/// front of its tier so that it is tried first next time.
+#[derive(Debug, Clone)]
+pub struct Announcer {
+    tiers: Vec<Vec<String>>,
+    // The torrent announced, taken from the metainfo the tiers come from.
+    info_hash: [u8; 20],
+    left: u64,
+}
+
+impl Announcer {
+    pub fn new(metainfo: &Metainfo) -> Self {
+        let mut tiers = metainfo.tracker_tiers();
+        for tier in &mut tiers {
+            fastrand::shuffle(tier);
+        }
+        Announcer {
+            tiers,
+            info_hash: metainfo.get_info_hash(),
+            // Nothing has been downloaded yet.
+            left: metainfo.info.total_length().unwrap_or(u64::MAX),
+        }
+    }
+
+    /// The trackers in the order they are tried next.
+    pub fn tiers(&self) -> &[Vec<String>] {
+        &self.tiers
+    }
+
+    /// Asks the trackers for peers, until one of them answers.
+    pub fn get_peers(&mut self) -> Result<Vec<SocketAddrV4>, AnnounceError> {
+        if self.tiers.is_empty() {
+            return Err(AnnounceError::NoTrackers);
+        }
+        let info_hash = urlencode(&self.info_hash);
+        let left = self.left;
+        let client = reqwest::blocking::Client::new();
+
+        announce_tiers(&mut self.tiers, |announce| {
+            request_peers(&client, announce, &info_hash, left)
+        })
+        .map_err(AnnounceError::NoAnswer)
+    }
+}
+
+/// Reasons [`Announcer::get_peers`] found no peers.
+#[derive(Debug)]
+pub enum AnnounceError {
+    /// The torrent lists no trackers, as trackerless (DHT) torrents do.
+    NoTrackers,
+
+    /// No tracker answered. Holds the URL of each one and why it failed.
+    NoAnswer(Vec<(String, String)>),
+}
+
+impl fmt::Display for AnnounceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnnounceError::NoTrackers => f.write_str("no trackers"),
+            AnnounceError::NoAnswer(failures) => {
+                f.write_str("no tracker answered")?;
+                for (url, err) in failures {
+                    write!(f, "; {}: {}", url, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnnounceError {}
+
+/// Calls `announce` on each tracker in turn until one succeeds, moving that
+/// tracker to the front of its tier. Returns every failure otherwise.
+fn announce_tiers<T, E>(
+    tiers: &mut [Vec<String>],
+    mut announce: impl FnMut(&str) -> Result<T, E>,
+) -> Result<T, Vec<(String, E)>> {
+    let mut failures = Vec::new();
+    for tier in tiers {
+        for index in 0..tier.len() {
+            match announce(&tier[index]) {
+                Ok(value) => {
+                    let url = tier.remove(index);
+                    tier.insert(0, url);
+                    return Ok(value);
+                }
+                Err(err) => failures.push((tier[index].clone(), err)),
+            }
+        }
+    }
+    Err(failures)
+}
+
+/// Sends an announce request to a single tracker.
+fn request_peers(
+    client: &reqwest::blocking::Client,
+    announce: &str,
+    info_hash: &str,
+    left: u64,
+) -> Result<Vec<SocketAddrV4>, String> {
+    let mut url = Url::parse(announce).map_err(|e| format!("Not a valid announce url: {}", e))?;
+    url.set_query(Some(&format!("info_hash={}", info_hash)));
+
+    let payload = tracker::Request {
+        peer_id: "-DE203s-x49Ta1Q*sgGQ",
+        port: 58438,
+        uploaded: 0,
+        downloaded: 0,
+        left,
+        compact: 1,
+    };
+
+    let input = client
+        .get(url)
+        .query(&payload)
+        .send()
+        .and_then(|res| res.bytes())
+        .map_err(|e| format!("Failed to establish connection to tracker: {}", e))?;
+    let de_res = bencode::from_bytes::<tracker::Response>(&input)
+        .map_err(|e| format!("Failed to deserialize tracker response: {}", e))?;
+
+    let chunked_peers = de_res.peers.chunks_exact(6);
+    let mut peers: Vec<SocketAddrV4> = Vec::new();
+    for peer in chunked_peers {
+        let ip: [u8; 4] = peer[..4].try_into().unwrap();
+        let ip = Ipv4Addr::from(ip);
+
+        let port: [u8; 2] = peer[4..6].try_into().unwrap();
+        let port = u16::from_be_bytes(port);
+
+        let socket = SocketAddrV4::new(ip, port);
+        peers.push(socket);
+    }
+    Ok(peers)
+}
+
+/// The `info` fields decoded by bendy, to compare with ours. bendy's serde
+/// support encodes `Option` as a list, so `files` and `length` default to
+/// empty instead.
+#[cfg(test)]
+#[derive(Deserialize)]
+struct BendyInfo {
+    name: String,
+    #[serde(rename = "piece length")]
+    piece_length: u32,
+    #[serde(default)]
+    length: u64,
+    #[serde(default)]
+    files: Vec<BendyFile>,
+    #[serde(with = "serde_bytes")]
+    pieces: Vec<u8>,
+}
+
+#[cfg(test)]
+#[derive(Deserialize)]
+struct BendyFile {
+    length: u64,
+    path: Vec<String>,
+}
+
+/// Extracts the encoded `info` dictionary with bendy's decoder.
+#[cfg(test)]
+fn bendy_raw_info(data: &[u8]) -> &[u8] {
+    let mut decoder = bendy::decoding::Decoder::new(data);
+    let mut dict = decoder
+        .next_object()
+        .unwrap()
+        .unwrap()
+        .try_into_dictionary()
+        .unwrap();
+    while let Some((key, value)) = dict.next_pair().unwrap() {
+        if key == b"info" {
+            return value.try_into_dictionary().unwrap().into_raw().unwrap();
+        }
+    }
+    panic!("no info dictionary");
+}
+
+#[test]
+fn test_matches_bendy() {
+    let data = include_bytes!("../../bencode/fixtures/single-file.torrent");
+    let metainfo = Metainfo::from_bytes(data).unwrap();
+
+    assert_eq!(
+        metainfo.announce.as_deref(),
+        Some("http://tracker.example.org:6969/announce")
+    );
+    assert_eq!(metainfo.info.name, "single.bin");
+    assert_eq!(metainfo.info.piece_length, 16384);
+    assert_eq!(metainfo.info.length, Some(100000));
+    assert_eq!(metainfo.info.pieces.len(), 7 * 20);
+
+    let raw_info = bendy_raw_info(data);
+    assert_eq!(bencode::to_bytes(&metainfo.info).unwrap(), raw_info);
+    let bendy_hash: [u8; 20] = Sha1::digest(raw_info).into();
+    assert_eq!(metainfo.get_info_hash(), bendy_hash);
+}
+
+#[test]
+fn test_real_torrents_match_bendy() {
+    // Debian's netinst torrent, as shipped in bendy's examples.
+    let data = include_bytes!("../../bencode/fixtures/debian-9.4.0-amd64-netinst.iso.torrent");
+    let metainfo = Metainfo::from_bytes(data).unwrap();
+    let bendy: BendyInfo = bendy::serde::from_bytes(bendy_raw_info(data)).unwrap();
+    assert_eq!(metainfo.info.name, "debian-9.4.0-amd64-netinst.iso");
+    assert_eq!(metainfo.info.name, bendy.name);
+    assert_eq!(metainfo.info.piece_length, bendy.piece_length);
+    assert_eq!(metainfo.info.length, Some(bendy.length));
+    assert_eq!(metainfo.info.pieces, bendy.pieces);
+    let bendy_hash: [u8; 20] = Sha1::digest(bendy_raw_info(data)).into();
+    assert_eq!(metainfo.get_info_hash(), bendy_hash);
+
+    // A multi-file torrent of the bendy 0.3.3 sources, laid out as mktorrent
+    // writes them.
+    let data = include_bytes!("../../bencode/fixtures/bendy-0.3.3.torrent");
+    let metainfo = Metainfo::from_bytes(data).unwrap();
+    let bendy: BendyInfo = bendy::serde::from_bytes(bendy_raw_info(data)).unwrap();
+    assert_eq!(metainfo.info.name, "bendy-0.3.3");
+    assert_eq!(metainfo.info.name, bendy.name);
+    assert_eq!(metainfo.info.piece_length, bendy.piece_length);
+    assert_eq!(metainfo.info.pieces, bendy.pieces);
+    let files: Vec<(u64, Vec<String>)> = metainfo
+        .info
+        .files
+        .iter()
+        .flatten()
+        .map(|file| (file.length, file.path.clone()))
+        .collect();
+    let bendy_files: Vec<(u64, Vec<String>)> = bendy
+        .files
+        .into_iter()
+        .map(|file| (file.length, file.path))
+        .collect();
+    assert_eq!(files.len(), 25);
+    assert_eq!(files, bendy_files);
+    assert_eq!(metainfo.announce_list.len(), 2);
+    let bendy_hash: [u8; 20] = Sha1::digest(bendy_raw_info(data)).into();
+    assert_eq!(metainfo.get_info_hash(), bendy_hash);
+    assert_eq!(
+        bencode::to_bytes(&metainfo.info).unwrap(),
+        bendy_raw_info(data)
+    );
+}
+
+#[test]
+fn test_error_location() {
+    let data = b"d8:announce15:http://a.b/anno4:infod5:filesld6:lengthi-1e4:pathl1:aeee\
+4:name1:a12:piece lengthi16384e6:pieces0:ee";
+    let err = match Metainfo::from_bytes(data).unwrap_err() {
+        MetainfoError::Decode(err) => err,
+        err => panic!("unexpected error: {}", err),
+    };
+    assert_eq!(err.path().as_deref(), Some("info.files[0].length"));
+    assert_eq!(&data[err.offset().unwrap()..][..4], b"i-1e");
+}
+
+#[test]
+fn test_info_hash_covers_unknown_keys() {
+    let pieces = [0u8; 7 * 20];
+    let info = bencode::bencode! {
+        "length" => 100000,
+        "name" => "single.bin",
+        "piece length" => 16384,
+        "pieces" => (&pieces[..]),
+        "private" => 1,
+    };
+    let data = bencode::to_bytes(&bencode::bencode! {
+        "announce" => "http://tracker.example.org:6969/announce",
+        "info" => (info.clone()),
+    })
+    .unwrap();
+
+    let metainfo = Metainfo::from_bytes(&data).unwrap();
+    let expected: [u8; 20] = Sha1::digest(bencode::to_bytes(&info).unwrap()).into();
+    assert_eq!(metainfo.get_info_hash(), expected);
+    assert_eq!(Sha1::digest(bendy_raw_info(&data))[..], expected);
+
+    // Re-encoding the parsed `Info` drops `private` and changes the hash.
+    let reencoded: [u8; 20] = Sha1::digest(bencode::to_bytes(&metainfo.info).unwrap()).into();
+    assert_ne!(metainfo.get_info_hash(), reencoded);
+}
+
+#[test]
+fn test_file_entries() {
+    let data = include_bytes!("../../bencode/fixtures/single-file.torrent");
+    let metainfo = Metainfo::from_bytes(data).unwrap();
+    let entries: Vec<FileEntry> = metainfo.info.file_entries().collect();
+    assert_eq!(
+        entries,
+        [FileEntry {
+            path: PathBuf::from("single.bin"),
+            length: 100000,
+            offset: 0,
+            md5sum: None,
+        }]
+    );
+
+    let data = include_bytes!("../../bencode/fixtures/multi-file.torrent");
+    let metainfo = Metainfo::from_bytes(data).unwrap();
+    assert_eq!(metainfo.info.name, "multi");
+    assert_eq!(metainfo.info.length, None);
+    assert_eq!(metainfo.info.files.as_ref().map(Vec::len), Some(3));
+
+    let entries: Vec<(PathBuf, u64, u64)> = metainfo
+        .info
+        .file_entries()
+        .map(|file| (file.path, file.length, file.offset))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            (PathBuf::from("multi/README.txt"), 1234, 0),
+            (PathBuf::from("multi/data/part-1.bin"), 40000, 1234),
+            (PathBuf::from("multi/data/part-2.bin"), 70001, 41234),
+        ]
+    );
+}
+
+#[test]
+fn test_multi_file_matches_bendy() {
+    let data = include_bytes!("../../bencode/fixtures/multi-file.torrent");
+    let metainfo = Metainfo::from_bytes(data).unwrap();
+
+    let bendy_hash: [u8; 20] = Sha1::digest(bendy_raw_info(data)).into();
+    assert_eq!(metainfo.get_info_hash(), bendy_hash);
+
+    // `private` and `source` are not modelled, so re-encoding loses them.
+    let reencoded: [u8; 20] = Sha1::digest(bencode::to_bytes(&metainfo.info).unwrap()).into();
+    assert_ne!(reencoded, bendy_hash);
+}
+
+#[test]
+fn test_large_torrent() {
+    let pieces = [0u8; 8 * 20];
+    let data = bencode::to_bytes(&bencode::bencode! {
+        "announce" => "http://tracker.example.org:6969/announce",
+        "info" => {
+            "files" => [
+                { "length" => 5_000_000_000_i64, "path" => ["disc.iso"] },
+                { "length" => 3_000_000_000_i64, "path" => ["extras.iso"] },
+            ],
+            "name" => "large",
+            "piece length" => 1073741824,
+            "pieces" => (&pieces[..]),
+        },
+    })
+    .unwrap();
+
+    let metainfo = Metainfo::from_bytes(&data).unwrap();
+    let entries: Vec<(u64, u64)> = metainfo
+        .info
+        .file_entries()
+        .map(|file| (file.length, file.offset))
+        .collect();
+    assert_eq!(
+        entries,
+        [(5_000_000_000, 0), (3_000_000_000, 5_000_000_000)]
+    );
+
+    // A hand-built `Info` is not validated, so the iterator stops at the
+    // first file that does not start within `u64::MAX` bytes.
+    let file = |length| File {
+        length,
+        path: vec!["file".to_owned()],
+        md5sum: None,
+    };
+    let info = Info {
+        files: Some(vec![file(u64::MAX), file(1), file(1)]),
+        ..metainfo.info
+    };
+    let offsets: Vec<u64> = info.file_entries().map(|file| file.offset).collect();
+    assert_eq!(offsets, [0, u64::MAX]);
+    // Only the last piece depends on the total length.
+    assert_eq!(info.total_length(), None);
+    assert_eq!(info.piece_size(0), Some(1073741824));
+    assert_eq!(info.piece_size(7), None);
+    assert_eq!(info.blocks_in_piece(7, 1 << 14).count(), 0);
+}
+
+#[test]
+fn test_piece_geometry() {
+    let data = include_bytes!("../../bencode/fixtures/single-file.torrent");
+    let info = Metainfo::from_bytes(data).unwrap().info;
+    assert_eq!(info.total_length(), Some(100000));
+    assert_eq!(info.piece_count(), 7);
+    assert_eq!(info.piece_size(0), Some(16384));
+    assert_eq!(info.piece_size(6), Some(100000 - 6 * 16384));
+    assert_eq!(info.piece_size(7), None);
+    assert_eq!(
+        info.piece_hash(1).map(|hash| &hash[..]),
+        Some(&info.pieces[20..40])
+    );
+    assert_eq!(info.piece_hash(7), None);
+
+    let blocks: Vec<(u32, u32)> = info.blocks_in_piece(6, 1024).collect();
+    assert_eq!(blocks, [(0, 1024), (1024, 672)]);
+    assert_eq!(
+        info.blocks_in_piece(0, 16384).collect::<Vec<_>>(),
+        [(0, 16384)]
+    );
+    assert_eq!(info.blocks_in_piece(7, 16384).count(), 0);
 
-            let port: [u8; 2] = peer[4..6].try_into().unwrap();
-            let port = u16::from_be_bytes(port);
+    let data = include_bytes!("../../bencode/fixtures/multi-file.torrent");
+    let info = Metainfo::from_bytes(data).unwrap().info;
+    assert_eq!(info.total_length(), Some(111235));
+    assert_eq!(info.piece_size(6), Some(111235 - 6 * 16384));
 
-            let socket = SocketAddrV4::new(ip, port);
-            peers.push(socket);
+    let segments = |index| -> Vec<(PathBuf, u64, u32)> {
+        info.piece_files(index)
+            .into_iter()
+            .map(|segment| (segment.file.path, segment.offset, segment.length))
+            .collect()
+    };
+    assert_eq!(
+        segments(0),
+        [
+            (PathBuf::from("multi/README.txt"), 0, 1234),
+            (PathBuf::from("multi/data/part-1.bin"), 0, 15150),
+        ]
+    );
+    assert_eq!(
+        segments(2),
+        [
+            (PathBuf::from("multi/data/part-1.bin"), 31534, 8466),
+            (PathBuf::from("multi/data/part-2.bin"), 0, 7918),
+        ]
+    );
+    assert_eq!(
+        segments(6),
+        [(PathBuf::from("multi/data/part-2.bin"), 57070, 12931)]
+    );
+    assert_eq!(segments(7), []);
+}
+
+#[test]
+fn test_validation() {
+    fn parse(info: bencode::Value) -> Result<Metainfo, MetainfoError> {
+        let data = bencode::to_bytes(&bencode::bencode! {
+            "announce" => "http://tracker.example.org:6969/announce",
+            "info" => info,
+        })
+        .unwrap();
+        Metainfo::from_bytes(&data)
+    }
+
+    fn multi(path: &[&str]) -> bencode::Value {
+        let pieces = [0u8; 20];
+        bencode::bencode! {
+            "files" => [{ "length" => 10, "path" => (bencode::to_value(path).unwrap()) }],
+            "name" => "multi",
+            "piece length" => 16384,
+            "pieces" => (&pieces[..]),
         }
-        peers
     }
+
+    let pieces = [0u8; 2 * 20];
+    let single = |name: &str, piece_length: u32, length: i64, pieces: &[u8]| {
+        bencode::bencode! {
+            "length" => length,
+            "name" => name,
+            "piece length" => piece_length,
+            "pieces" => pieces,
+        }
+    };
+
+    assert!(parse(single("file", 16384, 20000, &pieces)).is_ok());
+    assert!(parse(multi(&["data", "file"])).is_ok());
+
+    assert!(matches!(
+        Metainfo::from_bytes(b"d8:announce"),
+        Err(MetainfoError::Decode(_))
+    ));
+    assert!(matches!(
+        parse(single("", 16384, 20000, &pieces)),
+        Err(MetainfoError::EmptyName)
+    ));
+    assert!(matches!(
+        parse(single("..", 16384, 20000, &pieces)),
+        Err(MetainfoError::ParentDirectory(_))
+    ));
+    assert!(matches!(
+        parse(single("file", 0, 20000, &pieces)),
+        Err(MetainfoError::InvalidPieceLength(0))
+    ));
+    assert!(matches!(
+        parse(single("file", 10000, 20000, &pieces)),
+        Err(MetainfoError::InvalidPieceLength(10000))
+    ));
+    assert!(matches!(
+        parse(single("file", 16384, 20000, &pieces[..39])),
+        Err(MetainfoError::InvalidPiecesLength(39))
+    ));
+    assert!(matches!(
+        parse(single("file", 16384, 40000, &pieces)),
+        Err(MetainfoError::PieceCountMismatch {
+            expected: 3,
+            actual: 2
+        })
+    ));
+
+    assert!(matches!(parse(multi(&[])), Err(MetainfoError::EmptyPath)));
+    assert!(matches!(
+        parse(multi(&["data", ""])),
+        Err(MetainfoError::EmptyPathSegment(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["..", "etc", "passwd"])),
+        Err(MetainfoError::ParentDirectory(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["data", "."])),
+        Err(MetainfoError::CurrentDirectory(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["/etc", "passwd"])),
+        Err(MetainfoError::AbsolutePath(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["\\windows", "system32"])),
+        Err(MetainfoError::AbsolutePath(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["C:evil"])),
+        Err(MetainfoError::AbsolutePath(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["data/../../etc"])),
+        Err(MetainfoError::PathSeparator(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["..\\..\\evil"])),
+        Err(MetainfoError::PathSeparator(_))
+    ));
+    assert!(matches!(
+        parse(multi(&["a/b"])),
+        Err(MetainfoError::PathSeparator(_))
+    ));
+    assert!(matches!(
+        parse(single(".", 16384, 20000, &pieces)),
+        Err(MetainfoError::CurrentDirectory(_))
+    ));
+    assert!(matches!(
+        parse(single("dir/file", 16384, 20000, &pieces)),
+        Err(MetainfoError::PathSeparator(_))
+    ));
+
+    let mut info = single("file", 16384, 20000, &pieces);
+    info.as_dict_mut().unwrap().remove(&b"length"[..]);
+    assert!(matches!(parse(info), Err(MetainfoError::MissingLength)));
+
+    let mut info = multi(&["file"]);
+    info.as_dict_mut()
+        .unwrap()
+        .insert(b"length".to_vec(), bencode::Value::Int(10));
+    assert!(matches!(parse(info), Err(MetainfoError::LengthAndFiles)));
+
+    let info = bencode::bencode! {
+        "files" => [
+            { "length" => (i64::MAX), "path" => ["a"] },
+            { "length" => (i64::MAX), "path" => ["b"] },
+            { "length" => (i64::MAX), "path" => ["c"] },
+        ],
+        "name" => "multi",
+        "piece length" => 16384,
+        "pieces" => (&pieces[..]),
+    };
+    assert!(matches!(parse(info), Err(MetainfoError::LengthOverflow)));
+
+    let data = bencode::to_bytes(&bencode::bencode! {
+        "announce" => "not a url",
+        "info" => (single("file", 16384, 20000, &pieces)),
+    })
+    .unwrap();
+    let err = Metainfo::from_bytes(&data).unwrap_err();
+    assert!(matches!(err, MetainfoError::InvalidAnnounceUrl(ref url, _) if url == "not a url"));
+    assert!(err
+        .to_string()
+        .starts_with("invalid announce url \"not a url\""));
+}
+
+#[test]
+fn test_announce_list() {
+    let pieces = [0u8; 20];
+    let info = bencode::bencode! {
+        "length" => 10,
+        "name" => "file",
+        "piece length" => 16384,
+        "pieces" => (&pieces[..]),
+    };
+    let data = bencode::to_bytes(&bencode::bencode! {
+        "announce-list" => [
+            ["http://a.example.org/announce", "http://b.example.org/announce"],
+            [],
+            ["udp://c.example.org:6969"],
+        ],
+        "info" => (info.clone()),
+    })
+    .unwrap();
+
+    let metainfo = Metainfo::from_bytes(&data).unwrap();
+    assert_eq!(metainfo.announce, None);
+    assert_eq!(metainfo.announce_list.len(), 3);
+    assert_eq!(bencode::to_bytes(&metainfo).unwrap(), data);
+
+    // Trackers are shuffled within their tier only, empty tiers are dropped.
+    let mut tiers = Announcer::new(&metainfo).tiers().to_vec();
+    tiers[0].sort();
+    assert_eq!(
+        tiers,
+        [
+            vec![
+                "http://a.example.org/announce",
+                "http://b.example.org/announce"
+            ],
+            vec!["udp://c.example.org:6969"],
+        ]
+    );
+
+    // `announce` alone makes a single tier.
+    let data = bencode::to_bytes(&bencode::bencode! {
+        "announce" => "http://tracker.example.org:6969/announce",
+        "info" => (info.clone()),
+    })
+    .unwrap();
+    let metainfo = Metainfo::from_bytes(&data).unwrap();
+    assert_eq!(
+        metainfo.tracker_tiers(),
+        [vec!["http://tracker.example.org:6969/announce"]]
+    );
+
+    // `announce` is ignored, and not validated, next to an `announce-list`.
+    let data = bencode::to_bytes(&bencode::bencode! {
+        "announce" => "not a url",
+        "announce-list" => [["http://a.example.org/announce"]],
+        "info" => (info.clone()),
+    })
+    .unwrap();
+    let metainfo = Metainfo::from_bytes(&data).unwrap();
+    assert_eq!(
+        metainfo.tracker_tiers(),
+        [vec!["http://a.example.org/announce"]]
+    );
+
+    // Trackerless torrents are accepted, and only fail to announce.
+    let data = bencode::to_bytes(&bencode::bencode! { "info" => (info.clone()) }).unwrap();
+    let metainfo = Metainfo::from_bytes(&data).unwrap();
+    assert!(metainfo.tracker_tiers().is_empty());
+    assert!(matches!(
+        metainfo.get_peers(),
+        Err(AnnounceError::NoTrackers)
+    ));
+
+    let data = bencode::to_bytes(&bencode::bencode! {
+        "announce-list" => [["http://a.example.org/announce"], ["b.example.org"]],
+        "info" => info,
+    })
+    .unwrap();
+    assert!(matches!(
+        Metainfo::from_bytes(&data),
+        Err(MetainfoError::InvalidAnnounceUrl(ref url, _)) if url == "b.example.org"
+    ));
+}
+
+#[test]
+fn test_announce_tiers() {
+    let mut tiers = vec![
+        vec!["a".to_string(), "b".to_string()],
+        vec!["c".to_string(), "d".to_string()],
+    ];
+
+    // The first tier is down, `d` answers and moves to the front of its tier.
+    let mut tried = Vec::new();
+    let result = announce_tiers(&mut tiers, |url| {
+        tried.push(url.to_string());
+        if url == "d" {
+            Ok(url.to_string())
+        } else {
+            Err(())
+        }
+    });
+    assert_eq!(result, Ok("d".to_string()));
+    assert_eq!(tried, ["a", "b", "c", "d"]);
+    assert_eq!(tiers, [["a", "b"], ["d", "c"]]);
+
+    // `b` is back up and answers before the second tier is tried.
+    let result = announce_tiers(&mut tiers, |url| match url {
+        "b" | "d" => Ok(url.to_string()),
+        _ => Err(()),
+    });
+    assert_eq!(result, Ok("b".to_string()));
+    assert_eq!(tiers, [["b", "a"], ["d", "c"]]);
+
+    let result = announce_tiers(&mut tiers, |url| Err::<(), _>(url.len()));
+    assert_eq!(
+        result,
+        Err(vec![
+            ("b".to_string(), 1),
+            ("a".to_string(), 1),
+            ("d".to_string(), 1),
+            ("c".to_string(), 1),
+        ])
+    );
 }
@@ -16,14 +16,14 @@ pub struct Request<'a> {
     pub port: u16,
 
     // The total amount uploaded so far, encoded in base ten ascii
-    pub uploaded: u32,
+    pub uploaded: u64,
 
     // The total amount downloaded so far
-    pub downloaded: u32,
+    pub downloaded: u64,
 
     // The number of bytes this peer still has to download,
     // encoded in base ten ascii.
-    pub left: u32,
+    pub left: u64,
 
     // This is an optional key which maps to
     // started, completed, or stopped
@@ -42,3 +42,16 @@ pub struct Response<'a> {
     #[serde(with = "serde_bytes")]
     pub peers: &'a [u8],
 }
+
+#[test]
+fn test_response_matches_bendy() {
+    let data = b"d8:intervali1800e5:peers12:\x7f\x00\x00\x01\x1a\xe1\x0a\x00\x00\x02\x1a\xe2e";
+    let response = bencode::from_bytes::<Response>(data).unwrap();
+    let reference = bendy::serde::from_bytes::<Response>(data).unwrap();
+
+    assert_eq!(response.interval, 1800);
+    assert_eq!(response.interval, reference.interval);
+    assert_eq!(response.peers, reference.peers);
+    assert_eq!(bencode::to_bytes(&response).unwrap(), data);
+    assert_eq!(bendy::serde::to_bytes(&reference).unwrap(), data);
+}